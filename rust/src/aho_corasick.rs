@@ -0,0 +1,167 @@
+//! Aho–Corasick automaton for finding every occurrence of a set of
+//! patterns in a single pass over the input.
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+
+/// A dictionary of patterns, each with an associated value, compiled
+/// into an automaton. Matching works on bytes, so match positions are
+/// byte offsets.
+#[derive(Debug)]
+pub struct AhoCorasick<T> {
+    nodes: Vec<Node>,
+    patterns: Vec<(usize, T)>,
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    transitions: HashMap<u8, usize>,
+    /// Longest proper suffix of this node's string that is also a
+    /// prefix of some pattern
+    fail: usize,
+    /// Patterns that end at this node, including ones reached by
+    /// following fail links
+    outputs: Vec<usize>,
+}
+
+/// A pattern found in the input, spanning `start..end`.
+#[derive(Debug, PartialEq)]
+pub struct Match<'a, T> {
+    pub start: usize,
+    pub end: usize,
+    pub value: &'a T,
+}
+
+// Deriving these would require T: Copy, but we only hold a reference
+impl<'a, T> Clone for Match<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for Match<'a, T> {}
+
+impl<T> AhoCorasick<T> {
+    pub fn new<'p, I>(patterns: I) -> Self
+    where
+        I: IntoIterator<Item = (&'p str, T)>,
+    {
+        let mut nodes = vec![Node::default()];
+        let mut pattern_values = Vec::new();
+
+        // Build the trie of patterns
+        for (pattern, value) in patterns {
+            assert!(!pattern.is_empty(), "can't match an empty pattern");
+            let mut current = 0;
+            for &byte in pattern.as_bytes() {
+                current = match nodes[current].transitions.get(&byte) {
+                    Some(&next) => next,
+                    None => {
+                        nodes.push(Node::default());
+                        let next = nodes.len() - 1;
+                        nodes[current].transitions.insert(byte, next);
+                        next
+                    }
+                };
+            }
+            nodes[current].outputs.push(pattern_values.len());
+            pattern_values.push((pattern.len(), value));
+        }
+
+        // Breadth first so a node's fail target is always finished
+        // before the node itself
+        let mut queue: VecDeque<usize> = nodes[0].transitions.values().copied().collect();
+        while let Some(node) = queue.pop_front() {
+            let transitions: Vec<(u8, usize)> = nodes[node].transitions.iter().map(|(&b, &n)| (b, n)).collect();
+            for (byte, child) in transitions {
+                let mut fail = nodes[node].fail;
+                let child_fail = loop {
+                    if let Some(&next) = nodes[fail].transitions.get(&byte) {
+                        break next;
+                    }
+                    if fail == 0 {
+                        break 0;
+                    }
+                    fail = nodes[fail].fail;
+                };
+
+                nodes[child].fail = child_fail;
+                let inherited = nodes[child_fail].outputs.clone();
+                nodes[child].outputs.extend(inherited);
+                queue.push_back(child);
+            }
+        }
+
+        AhoCorasick { nodes, patterns: pattern_values }
+    }
+
+    fn step(&self, mut state: usize, byte: u8) -> usize {
+        loop {
+            if let Some(&next) = self.nodes[state].transitions.get(&byte) {
+                return next;
+            }
+            if state == 0 {
+                return 0;
+            }
+            state = self.nodes[state].fail;
+        }
+    }
+
+    /// Every match in the input, including overlapping ones. Matches
+    /// are ordered by where they end.
+    pub fn find_overlapping<'a>(&'a self, haystack: &'a str) -> impl Iterator<Item = Match<'a, T>> + 'a {
+        haystack
+            .bytes()
+            .enumerate()
+            .scan(0, move |state, (i, byte)| {
+                *state = self.step(*state, byte);
+                Some((i + 1, *state))
+            })
+            .flat_map(move |(end, state)| {
+                self.nodes[state].outputs.iter().map(move |&pattern| {
+                    let (len, value) = &self.patterns[pattern];
+                    Match { start: end - len, end, value }
+                })
+            })
+    }
+
+    /// The leftmost and rightmost matches, found in a single pass.
+    /// Matches that start at the same position are broken by taking
+    /// the longer one.
+    pub fn first_and_last<'a>(&'a self, haystack: &'a str) -> Option<(Match<'a, T>, Match<'a, T>)> {
+        let mut first_and_last: Option<(Match<'a, T>, Match<'a, T>)> = None;
+        for m in self.find_overlapping(haystack) {
+            first_and_last = Some(match first_and_last {
+                None => (m, m),
+                Some((first, last)) => {
+                    let first = if (m.start, Reverse(m.end)) < (first.start, Reverse(first.end)) { m } else { first };
+                    let last = if (m.start, m.end) > (last.start, last.end) { m } else { last };
+                    (first, last)
+                }
+            });
+        }
+        first_and_last
+    }
+}
+
+#[test]
+fn test_find_overlapping() {
+    let matcher = AhoCorasick::new(vec![("he", 1), ("she", 2), ("his", 3), ("hers", 4)]);
+    let matches: Vec<(usize, usize, i32)> = matcher
+        .find_overlapping("ushers")
+        .map(|m| (m.start, m.end, *m.value))
+        .collect();
+    assert_eq!(matches, vec![(1, 4, 2), (2, 4, 1), (2, 6, 4)]);
+
+    assert_eq!(matcher.find_overlapping("xyz").count(), 0);
+}
+
+#[test]
+fn test_first_and_last() {
+    let matcher = AhoCorasick::new(vec![("abc", 'x'), ("b", 'y'), ("ab", 'z')]);
+    let (first, last) = matcher.first_and_last("abc").expect("no matches");
+    assert_eq!(first, Match { start: 0, end: 3, value: &'x' });
+    assert_eq!(last, Match { start: 1, end: 2, value: &'y' });
+
+    assert_eq!(matcher.first_and_last("cc"), None);
+}
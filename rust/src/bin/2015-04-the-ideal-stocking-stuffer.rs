@@ -24,33 +24,121 @@
 //
 // For part 2: 6 leading zeros.
 
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
 fn main() {
-    println!("part 1 {}", mine_santa_coin("iwrupvqb", false));
-    println!("part 2 {}", mine_santa_coin("iwrupvqb", true));
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    for (part, zero_nibbles) in [(1, 5), (2, 6)] {
+        // Single threaded first to compare against
+        let mut thread_counts = vec![1, threads];
+        thread_counts.dedup();
+        for threads in thread_counts {
+            let start = Instant::now();
+            let answer = mine_santa_coin("iwrupvqb", zero_nibbles, threads);
+            println!(
+                "part {} {} ({} zeros, {} threads, {:?})",
+                part,
+                answer,
+                zero_nibbles,
+                threads,
+                start.elapsed()
+            );
+        }
+    }
 }
 
-/// Find the lowest integer that, when appended to the given key,
-/// produces a hash that starts with 5 zeros.
-fn mine_santa_coin(key: &str, part2: bool) -> u32 {
-    let key_bytes: Vec<u8> = key.bytes().collect();
-    let mut i = 0;
+/// How many numbers a thread checks before grabbing the next range.
+const CHUNK_SIZE: u64 = 10_000;
+
+/// Finds the lowest number that, appended to the key in decimal, has an
+/// MD5 hash starting with `zero_nibbles` zero hex digits. Threads take
+/// turns grabbing the next unsearched range of numbers until a hit is
+/// found, and then finish any lower ranges they were already in.
+fn mine_santa_coin(key: &str, zero_nibbles: u32, threads: usize) -> u64 {
+    assert!(zero_nibbles <= 32, "an MD5 hash only has 32 hex digits");
+
+    let mut key_context = md5::Context::new();
+    key_context.consume(key);
+
+    let next_chunk = AtomicU64::new(0);
+    let lowest = AtomicU64::new(u64::MAX);
+
+    std::thread::scope(|s| {
+        for _ in 0..threads.max(1) {
+            s.spawn(|| {
+                let mut buf = [0; 20];
+                loop {
+                    let start = next_chunk.fetch_add(CHUNK_SIZE, Ordering::Relaxed);
+                    if start >= lowest.load(Ordering::Relaxed) {
+                        return;
+                    }
+
+                    for i in start..start + CHUNK_SIZE {
+                        let mut context = key_context.clone();
+                        context.consume(format_decimal(i, &mut buf));
+                        if has_leading_zero_nibbles(&context.compute().0, zero_nibbles) {
+                            lowest.fetch_min(i, Ordering::Relaxed);
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    lowest.into_inner()
+}
+
+#[test]
+fn test_mine_santa_coin() {
+    assert_eq!(mine_santa_coin("abcdef", 5, 1), 609043);
+    assert_eq!(mine_santa_coin("pqrstuv", 5, 4), 1048970);
+
+    // Checks numbers right at the start of the search too
+    for threads in [1, 3] {
+        assert_eq!(mine_santa_coin("abcdef", 0, threads), 0);
+        assert_eq!(mine_santa_coin("abcdef", 3, threads), 3337);
+    }
+}
+
+fn has_leading_zero_nibbles(digest: &[u8; 16], zero_nibbles: u32) -> bool {
+    let full_bytes = (zero_nibbles / 2) as usize;
+    if digest[..full_bytes].iter().any(|&b| b != 0) {
+        return false;
+    }
+    // An odd count means the high half of the next byte must be zero too
+    zero_nibbles % 2 == 0 || digest[full_bytes] >> 4 == 0
+}
+
+#[test]
+fn test_has_leading_zero_nibbles() {
+    let mut digest = [0xff; 16];
+    digest[0] = 0;
+    digest[1] = 0x0f;
+    assert!(has_leading_zero_nibbles(&digest, 3));
+    assert!(!has_leading_zero_nibbles(&digest, 4));
+    assert!(has_leading_zero_nibbles(&[0; 16], 32));
+}
+
+/// Writes `n` in decimal to the end of `buf` and returns the digits.
+fn format_decimal(mut n: u64, buf: &mut [u8; 20]) -> &[u8] {
+    let mut start = buf.len();
     loop {
-        let i_bytes: Vec<u8> = format!("{}", i).bytes().collect();
-        let full_data: Vec<u8> = key_bytes.iter().chain(&i_bytes).cloned().collect();
-        let digest = md5::compute(full_data);
-        if digest[0] == 0 && digest[1] == 0 {
-            // 5th zero is in 3rd u8, so for part 1 we need to bit
-            // shift to get it. For part 2 we can just read whole
-            // byte.
-            if (!part2 && (digest[2] >> 4) == 0) || (part2 && digest[2] == 0) {
-                return i;
-            }
+        start -= 1;
+        buf[start] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
         }
-        i += 1;
     }
+    &buf[start..]
 }
 
 #[test]
-fn test_mine_santa_coin() {
-    assert_eq!(mine_santa_coin("abcdef", false), 609043);
+fn test_format_decimal() {
+    let mut buf = [0; 20];
+    assert_eq!(format_decimal(0, &mut buf), b"0");
+    assert_eq!(format_decimal(609043, &mut buf), b"609043");
+    assert_eq!(format_decimal(u64::MAX, &mut buf), u64::MAX.to_string().as_bytes());
 }
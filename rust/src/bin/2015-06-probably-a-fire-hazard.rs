@@ -3,57 +3,235 @@ use itertools::Itertools;
 fn main() {
     let instructions = parse_input(INPUT);
 
-    let mut part1_grid = vec![vec![false; 1000]; 1000];
-    for instruction in &instructions {
-        apply_instruction_part1(&mut part1_grid, instruction);
+    let part1_grid = LightGrid::<OnOff>::new(&instructions);
+    println!("part1: {}", part1_grid.total());
+
+    let part2_grid = LightGrid::<Brightness>::new(&instructions);
+    println!("part2: {}", part2_grid.total());
+
+    // Optionally save what the lights look like
+    if let Some(path) = std::env::args().nth(1) {
+        let image = part1_grid.to_pgm(1000);
+        std::fs::write(&path, image).unwrap_or_else(|err| panic!("couldn't write {}: {}", path, err));
     }
-    let part1 = part1_grid
-        .iter()
-        .flatten()
-        .filter(|x| **x)
-        .count();
-    println!("part1: {}", part1);
-
-    let mut part2_grid = vec![vec![0; 1000]; 1000];
-    for instruction in &instructions {
-        apply_instruction_part2(&mut part2_grid, instruction);
+}
+
+/// How instructions change a single light.
+trait LightRules {
+    type Light: Copy + Default;
+
+    fn apply(light: Self::Light, instruction_type: &InstructionType) -> Self::Light;
+
+    /// How much a light counts towards the total.
+    fn value(light: Self::Light) -> u64;
+}
+
+/// Part 1, where lights are just on or off.
+struct OnOff;
+
+impl LightRules for OnOff {
+    type Light = bool;
+
+    fn apply(light: bool, instruction_type: &InstructionType) -> bool {
+        match instruction_type {
+            InstructionType::Toggle => !light,
+            InstructionType::On => true,
+            InstructionType::Off => false,
+        }
+    }
+
+    fn value(light: bool) -> u64 {
+        light as u64
     }
-    let part2 = part2_grid
-        .iter()
-        .flatten()
-        .sum::<u64>();
-    println!("part2: {}", part2);
 }
 
-fn apply_instruction_part1(grid: &mut [Vec<bool>], instruction: &Instruction) {
-    for i in instruction.start.0..=instruction.end.0 {
-        for j in instruction.start.1..=instruction.end.1 {
-            grid[i][j] = match instruction.instruction_type {
-                InstructionType::Toggle => !grid[i][j],
-                InstructionType::On => true,
-                InstructionType::Off => false,
+/// Part 2, where instructions change each light's brightness.
+struct Brightness;
+
+impl LightRules for Brightness {
+    type Light = u64;
+
+    fn apply(light: u64, instruction_type: &InstructionType) -> u64 {
+        match instruction_type {
+            InstructionType::Toggle => light + 2,
+            InstructionType::On => light + 1,
+            InstructionType::Off => light.saturating_sub(1),
+        }
+    }
+
+    fn value(light: u64) -> u64 {
+        light
+    }
+}
+
+/// The lights after following some instructions. Only coordinates where
+/// some instruction's rectangle starts or ends matter, so the grid is
+/// stored as blocks between those, with one light value per block.
+struct LightGrid<R: LightRules> {
+    // Block i covers xs[i]..xs[i + 1], and the same for ys
+    xs: Vec<u64>,
+    ys: Vec<u64>,
+    blocks: Vec<R::Light>,
+}
+
+impl<R: LightRules> LightGrid<R> {
+    fn new(instructions: &[Instruction]) -> Self {
+        let breakpoints = |coord: fn(&(u64, u64)) -> u64| -> Vec<u64> {
+            instructions
+                .iter()
+                .flat_map(|instruction| [coord(&instruction.start), coord(&instruction.end) + 1])
+                .sorted_unstable()
+                .dedup()
+                .collect()
+        };
+        let xs = breakpoints(|point| point.0);
+        let ys = breakpoints(|point| point.1);
+
+        let num_blocks = xs.len().saturating_sub(1) * ys.len().saturating_sub(1);
+        let mut grid = LightGrid { xs, ys, blocks: vec![R::Light::default(); num_blocks] };
+        for instruction in instructions {
+            grid.apply(instruction);
+        }
+        grid
+    }
+
+    fn apply(&mut self, instruction: &Instruction) {
+        let block_index = |bounds: &[u64], coord| bounds.binary_search(&coord).expect("coordinate isn't a breakpoint");
+        let x_blocks = block_index(&self.xs, instruction.start.0)..block_index(&self.xs, instruction.end.0 + 1);
+        let y_blocks = block_index(&self.ys, instruction.start.1)..block_index(&self.ys, instruction.end.1 + 1);
+
+        let row_len = self.ys.len() - 1;
+        for i in x_blocks {
+            for block in &mut self.blocks[i * row_len + y_blocks.start..i * row_len + y_blocks.end] {
+                *block = R::apply(*block, &instruction.instruction_type);
             }
         }
     }
+
+    /// Value of a single light.
+    fn light(&self, x: u64, y: u64) -> R::Light {
+        // Index of the block containing the coordinate, if any
+        let block_index = |bounds: &[u64], coord| match bounds.binary_search(&coord) {
+            Ok(i) if i + 1 < bounds.len() => Some(i),
+            Ok(_) => None,
+            Err(i) if i > 0 && i < bounds.len() => Some(i - 1),
+            Err(_) => None,
+        };
+        match (block_index(&self.xs, x), block_index(&self.ys, y)) {
+            (Some(i), Some(j)) => self.blocks[i * (self.ys.len() - 1) + j],
+            _ => R::Light::default(),
+        }
+    }
+
+    fn total(&self) -> u128 {
+        let mut total = 0;
+        for (i, (x0, x1)) in self.xs.iter().tuple_windows().enumerate() {
+            for (j, (y0, y1)) in self.ys.iter().tuple_windows().enumerate() {
+                let value = R::value(self.blocks[i * (self.ys.len() - 1) + j]) as u128;
+                total += value * (x1 - x0) as u128 * (y1 - y0) as u128;
+            }
+        }
+        total
+    }
+
+    /// Renders the lights from (0, 0) to the largest coordinate as a
+    /// binary PGM image, scaled down to at most `max_size` pixels on a
+    /// side by sampling. The brightest light is white.
+    fn to_pgm(&self, max_size: u64) -> Vec<u8> {
+        let extent = self.xs.last().max(self.ys.last()).copied().unwrap_or(0).max(1);
+        let size = extent.min(max_size);
+        let brightest = self.blocks.iter().map(|&light| R::value(light)).max().unwrap_or(0).max(1);
+
+        let mut image = format!("P5\n{} {}\n255\n", size, size).into_bytes();
+        for py in 0..size {
+            for px in 0..size {
+                let light = self.light(px * extent / size, py * extent / size);
+                image.push((R::value(light) * 255 / brightest) as u8);
+            }
+        }
+        image
+    }
+}
+
+#[test]
+fn test_light_grid() {
+    let instructions = parse_input(
+        "turn on 0,0 through 999,999
+toggle 0,0 through 999,0
+turn off 499,499 through 500,500",
+    );
+    assert_eq!(LightGrid::<OnOff>::new(&instructions).total(), 1000000 - 1000 - 4);
+    assert_eq!(LightGrid::<Brightness>::new(&instructions).total(), 1000000 + 2000 - 4);
+
+    let instructions = parse_input("turn on 0,0 through 0,0\ntoggle 0,0 through 999,999");
+    assert_eq!(LightGrid::<Brightness>::new(&instructions).total(), 2000001);
+
+    // Far bigger than could fit in memory one light at a time
+    let instructions = parse_input(
+        "turn on 0,0 through 999999999,999999999
+toggle 1,1 through 999999998,999999998",
+    );
+    let grid = LightGrid::<OnOff>::new(&instructions);
+    assert_eq!(grid.total(), 4 * 999999999);
+    assert!(grid.light(0, 5) && !grid.light(5, 5) && !grid.light(1000000000, 0));
 }
 
-fn apply_instruction_part2(grid: &mut [Vec<u64>], instruction: &Instruction) {
-    for i in instruction.start.0..=instruction.end.0 {
-        for j in instruction.start.1..=instruction.end.1 {
-            grid[i][j] = match instruction.instruction_type {
-                InstructionType::Toggle => grid[i][j] + 2,
-                InstructionType::On => grid[i][j] + 1,
-                InstructionType::Off => grid[i][j].saturating_sub(1),
+#[test]
+fn test_light_grid_matches_full_grid() {
+    let instructions = parse_input(INPUT);
+    let compressed = LightGrid::<OnOff>::new(&instructions);
+    let brightness = LightGrid::<Brightness>::new(&instructions);
+
+    let mut full = vec![vec![false; 1000]; 1000];
+    let mut full_brightness = vec![vec![0; 1000]; 1000];
+    for instruction in &instructions {
+        for i in instruction.start.0..=instruction.end.0 {
+            for j in instruction.start.1..=instruction.end.1 {
+                let (i, j) = (i as usize, j as usize);
+                full[i][j] = OnOff::apply(full[i][j], &instruction.instruction_type);
+                full_brightness[i][j] = Brightness::apply(full_brightness[i][j], &instruction.instruction_type);
             }
         }
     }
+
+    for x in (0..1000).step_by(7) {
+        for y in 0..1000 {
+            assert_eq!(compressed.light(x, y), full[x as usize][y as usize]);
+            assert_eq!(brightness.light(x, y), full_brightness[x as usize][y as usize]);
+        }
+    }
+    assert_eq!(compressed.total(), full.iter().flatten().filter(|x| **x).count() as u128);
+    assert_eq!(brightness.total(), full_brightness.iter().flatten().sum::<u64>() as u128);
+}
+
+#[test]
+fn test_to_pgm() {
+    let instructions = parse_input("turn on 0,0 through 1,0\ntoggle 1,0 through 1,1\nturn on 2,2 through 2,2");
+    let mut expected = b"P5\n3 3\n255\n".to_vec();
+    expected.extend([255, 0, 0, 0, 255, 0, 0, 0, 255]);
+    assert_eq!(LightGrid::<OnOff>::new(&instructions).to_pgm(10), expected);
+
+    let pgm = LightGrid::<Brightness>::new(&instructions).to_pgm(10);
+    assert_eq!(&pgm[pgm.len() - 9..], &[85, 255, 0, 0, 170, 0, 0, 0, 85]);
+}
+
+#[test]
+#[should_panic(expected = "start of rectangle is after its end: turn on 0,5 through 9,4")]
+fn test_parse_reversed_rectangle() {
+    parse_instruction("turn on 0,5 through 9,4");
+}
+
+#[test]
+#[should_panic(expected = "rectangle ends at u64::MAX: toggle 0,0 through 18446744073709551615,0")]
+fn test_parse_rectangle_at_max() {
+    parse_instruction("toggle 0,0 through 18446744073709551615,0");
 }
 
 #[derive(Debug)]
 struct Instruction {
     instruction_type: InstructionType,
-    start: (usize, usize),
-    end: (usize, usize),
+    start: (u64, u64),
+    end: (u64, u64),
 }
 
 #[derive(Debug)]
@@ -91,10 +269,19 @@ fn parse_instruction(line: &str) -> Instruction {
     };
     let (start_x_str, start_y_str) = start_str.split_once(",").expect("no , in start");
     let (end_x_str, end_y_str) = end_str.split_once(",").expect("no , in end");
-    let start_x = start_x_str.parse::<usize>().expect("start_x");
-    let start_y = start_y_str.parse::<usize>().expect("start_y");
-    let end_x = end_x_str.parse::<usize>().expect("end_x");
-    let end_y = end_y_str.parse::<usize>().expect("end_y");
+    let start_x = start_x_str.parse::<u64>().expect("start_x");
+    let start_y = start_y_str.parse::<u64>().expect("start_y");
+    let end_x = end_x_str.parse::<u64>().expect("end_x");
+    let end_y = end_y_str.parse::<u64>().expect("end_y");
+
+    // LightGrid splits the grid at one past each end coordinate, and
+    // expects rectangles to go from their smallest to largest coordinates
+    assert!(
+        start_x <= end_x && start_y <= end_y,
+        "start of rectangle is after its end: {}",
+        line
+    );
+    assert!(end_x < u64::MAX && end_y < u64::MAX, "rectangle ends at u64::MAX: {}", line);
 
     Instruction {
         instruction_type,
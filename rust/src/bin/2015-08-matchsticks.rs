@@ -1,84 +1,141 @@
+use std::fmt;
+
 fn main() {
-    let parsed = INPUT
-        .lines()
-        .map(parse_string)
-        .collect::<Vec<ParsedString>>();
-    let part1: usize = parsed
+    let literals: Vec<&str> = INPUT.lines().collect();
+
+    let part1: usize = literals
         .iter()
-        .map(|p| p.literal.len() - p.parsed.len())
+        .map(|literal| {
+            let decoded = decode(literal).unwrap_or_else(|err| panic!("{}: {}", literal, err));
+            literal.len() - decoded.len()
+        })
         .sum();
     println!("part1: {}", part1);
+
+    let part2: usize = literals
+        .iter()
+        .map(|literal| encode(literal.as_bytes()).len() - literal.len())
+        .sum();
+    println!("part2: {}", part2);
 }
 
 #[derive(Debug, PartialEq)]
-struct ParsedString {
-    literal: Vec<char>,
-    parsed: Vec<char>,
+struct DecodeError {
+    /// 1-based, counted in bytes
+    column: usize,
+    message: String,
 }
 
-fn parse_string(input: &str) -> ParsedString {
-    let literal: Vec<char> = input.chars().collect();
-    assert_eq!(literal[0], '"');
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
 
-    let mut parsed = Vec::new();
+fn decode_error(pos: usize, message: &str) -> DecodeError {
+    DecodeError { column: pos + 1, message: message.to_string() }
+}
 
+/// Decodes a quoted string literal into the bytes it represents. The
+/// only escapes are `\\`, `\"` and `\xNN` for a byte in hex.
+fn decode(literal: &str) -> Result<Vec<u8>, DecodeError> {
+    let bytes = literal.as_bytes();
+    if bytes.first() != Some(&b'"') {
+        return Err(decode_error(0, "expected opening quote"));
+    }
+
+    let mut decoded = Vec::new();
     let mut i = 1;
-    while i < literal.len() {
-        match literal[i] {
-            '\\' => {
-                i += 1;
-                match literal[i] {
-                    '\\' => {
-                        parsed.push('\\');
-                        i += 1;
-                    },
-                    '"' => {
-                        parsed.push('"');
-                        i += 1;
-                    },
-                    'x' => {
-                        let hex_seq = literal[i+1..i+3]
-                            .iter()
-                            .collect::<String>();
-                        let hex_val = u8::from_str_radix(hex_seq.as_str(), 16).expect("parse hex escape");
-                        parsed.push(hex_val as char);
-                        i += 3;
+    loop {
+        match bytes.get(i) {
+            None => return Err(decode_error(i, "missing closing quote")),
+            Some(b'"') => break,
+            Some(b'\\') => match bytes.get(i + 1) {
+                Some(b'\\') => {
+                    decoded.push(b'\\');
+                    i += 2;
+                }
+                Some(b'"') => {
+                    decoded.push(b'"');
+                    i += 2;
+                }
+                Some(b'x') => {
+                    let digits = bytes.get(i + 2..i + 4).unwrap_or_default();
+                    if digits.len() != 2 || !digits.iter().all(u8::is_ascii_hexdigit) {
+                        return Err(decode_error(i, "\\x must be followed by two hex digits"));
                     }
-                    c => panic!("unknown escape sequence char {}", c),
+                    let hex = std::str::from_utf8(digits).expect("hex digits are ASCII");
+                    decoded.push(u8::from_str_radix(hex, 16).expect("parse hex escape"));
+                    i += 4;
                 }
-            }
-            '"' => {
-                assert_eq!(i, literal.len() - 1);
+                Some(_) => return Err(decode_error(i, "unknown escape sequence")),
+                None => return Err(decode_error(i, "unterminated escape sequence")),
+            },
+            Some(&b) => {
+                decoded.push(b);
                 i += 1;
             }
-            c => {
-                parsed.push(c);
-                i += 1;
-            },
         }
     }
 
-    ParsedString { literal, parsed }
+    if i != bytes.len() - 1 {
+        return Err(decode_error(i + 1, "unexpected input after closing quote"));
+    }
+
+    Ok(decoded)
 }
 
 #[test]
-fn test_parse_string() {
-    assert_eq!(parse_string(r#""""#), ParsedString {
-        literal: vec!['"', '"'],
-        parsed: vec![],
-    });
-    assert_eq!(parse_string(r#""abc""#), ParsedString {
-        literal: vec!['"', 'a', 'b', 'c', '"'],
-        parsed: vec!['a', 'b', 'c'],
-    });
-    assert_eq!(parse_string(r#""a\"b\\c""#), ParsedString {
-        literal: vec!['"', 'a', '\\', '"', 'b', '\\', '\\', 'c', '"'],
-        parsed: vec!['a', '"', 'b', '\\', 'c'],
-    });
-    assert_eq!(parse_string(r#""\x27""#), ParsedString {
-        literal: vec!['"', '\\', 'x', '2', '7', '"'],
-        parsed: vec!['\''],
-    });
+fn test_decode() {
+    assert_eq!(decode(r#""""#), Ok(vec![]));
+    assert_eq!(decode(r#""abc""#), Ok(b"abc".to_vec()));
+    assert_eq!(decode(r#""a\"b\\c""#), Ok(b"a\"b\\c".to_vec()));
+    assert_eq!(decode(r#""\x27""#), Ok(b"'".to_vec()));
+    assert_eq!(decode(r#""\xfF""#), Ok(vec![0xff]));
+
+    let error_column = |literal| decode(literal).unwrap_err().column;
+    assert_eq!(error_column(r#"abc""#), 1);
+    assert_eq!(error_column(r#""abc"#), 5);
+    assert_eq!(error_column(r#""ab\c""#), 4);
+    assert_eq!(error_column(r#""ab\"#), 4);
+    assert_eq!(error_column(r#""ab\x4""#), 4);
+    assert_eq!(error_column(r#""ab\xzz""#), 4);
+    assert_eq!(error_column(r#""ab"c""#), 5);
+}
+
+/// Encodes bytes as a quoted string literal that `decode` turns back
+/// into the same bytes. Anything outside of printable ASCII is written
+/// as a hex escape.
+fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len() + 2);
+    encoded.push('"');
+    for &b in bytes {
+        match b {
+            b'"' => encoded.push_str("\\\""),
+            b'\\' => encoded.push_str("\\\\"),
+            b' '..=b'~' => encoded.push(b as char),
+            _ => encoded.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    encoded.push('"');
+    encoded
+}
+
+#[test]
+fn test_encode() {
+    assert_eq!(encode(br#""""#), r#""\"\"""#);
+    assert_eq!(encode(br#""abc""#), r#""\"abc\"""#);
+    assert_eq!(encode(br#""aaa\"aaa""#), r#""\"aaa\\\"aaa\"""#);
+    assert_eq!(encode(br#""\x27""#), r#""\"\\x27\"""#);
+    assert_eq!(encode(&[0, b'a', 0x7f, 0xff]), r#""\x00a\x7f\xff""#);
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn test_encode_round_trip(bytes in proptest::collection::vec(proptest::prelude::any::<u8>(), 0..64)) {
+        proptest::prop_assert_eq!(decode(&encode(&bytes)), Ok(bytes));
+    }
 }
 
 const INPUT: &str = r#""qxfcsmh"
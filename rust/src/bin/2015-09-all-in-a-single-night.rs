@@ -1,10 +1,14 @@
-use itertools::Itertools;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+use std::fmt;
 
 fn main() {
     let input = parse_input(INPUT);
-    println!("part1 {:#?}", min_or_max_length(&input, true));
-    println!("part2 {:#?}", min_or_max_length(&input, false));
+    let graph = CityGraph::new(&input);
+    for (part, objective) in [(1, Objective::Shortest), (2, Objective::Longest)] {
+        let route = best_route(&graph, objective, Tour::Open).expect("no route visits every city");
+        println!("part{} {}", part, route.length);
+        println!("  {}", graph.display_route(&route));
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -14,22 +18,229 @@ struct CityDistance {
     distance: u64,
 }
 
-fn min_or_max_length(distances: &[CityDistance], do_min: bool) -> u64 {
-    // Collect all cities and inter-city distances
-    let mut cities: HashSet<String> = HashSet::new();
-    let mut distance_map: HashMap<(&String, &String), u64> = HashMap::new();
-    for CityDistance { start, end, distance } in distances {
-        cities.insert(start.clone());
-        cities.insert(end.clone());
-        distance_map.insert((start, end), *distance);
-        distance_map.insert((end, start), *distance);
+fn intern_city<'a>(name: &'a str, indexes: &mut HashMap<&'a str, usize>, names: &mut Vec<String>) -> usize {
+    *indexes.entry(name).or_insert_with(|| {
+        names.push(name.to_string());
+        names.len() - 1
+    })
+}
+
+/// Cities by index, with the distance between each pair if there is one.
+struct CityGraph {
+    names: Vec<String>,
+    distances: Vec<Vec<Option<u64>>>,
+}
+
+impl CityGraph {
+    fn new(distances: &[CityDistance]) -> Self {
+        let mut indexes: HashMap<&str, usize> = HashMap::new();
+        let mut names = Vec::new();
+        let mut edges = Vec::new();
+        for CityDistance { start, end, distance } in distances {
+            let start = intern_city(start, &mut indexes, &mut names);
+            let end = intern_city(end, &mut indexes, &mut names);
+            edges.push((start, end, *distance));
+        }
+
+        let mut matrix = vec![vec![None; names.len()]; names.len()];
+        for (a, b, distance) in edges {
+            matrix[a][b] = Some(distance);
+            matrix[b][a] = Some(distance);
+        }
+        CityGraph { names, distances: matrix }
+    }
+
+    fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    fn distance(&self, a: usize, b: usize) -> Option<u64> {
+        self.distances[a][b]
+    }
+
+    /// Length of a route, or `None` if it uses a missing road.
+    fn route_length(&self, cities: &[usize], tour: Tour) -> Option<u64> {
+        let mut length = 0;
+        for pair in cities.windows(2) {
+            length += self.distance(pair[0], pair[1])?;
+        }
+        if tour == Tour::Closed && cities.len() > 1 {
+            length += self.distance(cities[cities.len() - 1], cities[0])?;
+        }
+        Some(length)
+    }
+
+    fn display_route<'a>(&'a self, route: &'a Route) -> impl fmt::Display + 'a {
+        RouteDisplay { graph: self, route }
+    }
+}
+
+struct RouteDisplay<'a> {
+    graph: &'a CityGraph,
+    route: &'a Route,
+}
+
+impl fmt::Display for RouteDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = self.route.cities.iter().map(|&city| self.graph.names[city].as_str()).collect();
+        write!(f, "{}", names.join(" -> "))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Objective {
+    Shortest,
+    Longest,
+}
+
+impl Objective {
+    fn is_better(self, a: u64, b: u64) -> bool {
+        match self {
+            Objective::Shortest => a < b,
+            Objective::Longest => a > b,
+        }
+    }
+}
+
+/// Whether the route has to end up back where it started.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tour {
+    Open,
+    Closed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Route {
+    cities: Vec<usize>,
+    length: u64,
+}
+
+/// Finds the best route exactly if there are few enough cities, and
+/// otherwise a good one.
+fn best_route(graph: &CityGraph, objective: Objective, tour: Tour) -> Option<Route> {
+    if graph.len() <= MAX_EXACT_CITIES {
+        held_karp(graph, objective, tour)
+    } else {
+        two_opt(graph, objective, tour)
+    }
+}
+
+/// Held-Karp needs space for every subset of cities, which is 8 MB for
+/// this many.
+const MAX_EXACT_CITIES: usize = 16;
+
+/// Marks paths in `held_karp` that haven't been found.
+const UNREACHED: u64 = u64::MAX;
+
+/// Finds the best route that visits every city once with dynamic
+/// programming over subsets: the best path through a set of cities that
+/// ends at a given city is built from the best paths through that set
+/// minus the last city.
+fn held_karp(graph: &CityGraph, objective: Objective, tour: Tour) -> Option<Route> {
+    let n = graph.len();
+    assert!(n <= MAX_EXACT_CITIES, "too many cities for an exact solution, use two_opt");
+    if n == 0 {
+        return None;
     }
 
-    // Iterate over all permutations of cities
-    let lengths = cities
-        .iter()
-        .permutations(cities.len())
-        .flat_map(|path| path_length(&distance_map, &path));
+    // best[visited * n + last] is the best length of a path through the
+    // cities in `visited` that ends at `last`, and previous at the same
+    // index is the city before `last` on that path.
+    let index = |visited: usize, last: usize| visited * n + last;
+    let mut best = vec![UNREACHED; n << n];
+    let mut previous = vec![0u8; n << n];
+    match tour {
+        Tour::Open => (0..n).for_each(|city| best[index(1 << city, city)] = 0),
+        // A closed tour can start anywhere, so always start at city 0
+        Tour::Closed => best[index(1, 0)] = 0,
+    }
+
+    for visited in 1..(1usize << n) {
+        for last in 0..n {
+            let length = best[index(visited, last)];
+            if length == UNREACHED {
+                continue;
+            }
+            for next in (0..n).filter(|next| visited & (1 << next) == 0) {
+                let distance = match graph.distance(last, next) {
+                    Some(distance) => distance,
+                    None => continue,
+                };
+                let slot = index(visited | (1 << next), next);
+                if best[slot] == UNREACHED || objective.is_better(length + distance, best[slot]) {
+                    best[slot] = length + distance;
+                    previous[slot] = last as u8;
+                }
+            }
+        }
+    }
+
+    // Pick the best ending city, including the way home for a closed tour
+    let all = (1 << n) - 1;
+    let (length, last) = (0..n)
+        .filter_map(|last| {
+            let length = best[index(all, last)];
+            if length == UNREACHED {
+                return None;
+            }
+            match tour {
+                Tour::Open => Some((length, last)),
+                Tour::Closed if n == 1 => Some((length, last)),
+                Tour::Closed => Some((length + graph.distance(last, 0)?, last)),
+            }
+        })
+        .reduce(|a, b| if objective.is_better(b.0, a.0) { b } else { a })?;
+
+    let mut cities = vec![last];
+    let mut visited = all;
+    while visited.count_ones() > 1 {
+        let city = *cities.last().unwrap();
+        cities.push(previous[index(visited, city)] as usize);
+        visited &= !(1 << city);
+    }
+    cities.reverse();
+
+    Some(Route { cities, length })
+}
+
+#[test]
+fn test_held_karp() {
+    let graph = CityGraph::new(&parse_input(_TEST_INPUT));
+    let shortest = held_karp(&graph, Objective::Shortest, Tour::Open).unwrap();
+    assert_eq!(shortest.length, 605);
+    let route = graph.display_route(&shortest).to_string();
+    assert!(["London -> Dublin -> Belfast", "Belfast -> Dublin -> London"].contains(&route.as_str()));
+    assert_eq!(held_karp(&graph, Objective::Longest, Tour::Open).unwrap().length, 982);
+    assert_eq!(held_karp(&graph, Objective::Shortest, Tour::Closed).unwrap().length, 1123);
+
+    // Check against trying every order
+    let input = parse_input(INPUT);
+    let graph = CityGraph::new(&input);
+    for (objective, do_min) in [(Objective::Shortest, true), (Objective::Longest, false)] {
+        for tour in [Tour::Open, Tour::Closed] {
+            let route = held_karp(&graph, objective, tour).unwrap();
+            assert_eq!(graph.route_length(&route.cities, tour), Some(route.length));
+            assert_eq!(route.length, brute_force_length(&graph, do_min, tour));
+        }
+    }
+
+    // Missing roads
+    let graph = CityGraph::new(&parse_input("A to B = 1\nB to C = 2\nC to D = 3\nA to D = 10"));
+    assert_eq!(held_karp(&graph, Objective::Shortest, Tour::Open).unwrap().length, 6);
+    assert_eq!(held_karp(&graph, Objective::Longest, Tour::Open).unwrap().length, 15);
+    assert_eq!(held_karp(&graph, Objective::Shortest, Tour::Closed).unwrap().length, 16);
+    let graph = CityGraph::new(&parse_input("A to B = 1\nA to C = 2\nA to D = 3"));
+    assert_eq!(held_karp(&graph, Objective::Shortest, Tour::Open), None);
+}
+
+/// The original solution, which tries every order of cities.
+#[cfg(test)]
+fn brute_force_length(graph: &CityGraph, do_min: bool, tour: Tour) -> u64 {
+    use itertools::Itertools;
+
+    let lengths = (0..graph.len())
+        .permutations(graph.len())
+        .flat_map(|path| graph.route_length(&path, tour));
     if do_min {
         lengths.min().expect("no path distances")
     } else {
@@ -37,12 +248,116 @@ fn min_or_max_length(distances: &[CityDistance], do_min: bool) -> u64 {
     }
 }
 
-fn path_length(distances: &HashMap<(&String, &String), u64>, path: &[&String]) -> Option<u64> {
-    let mut path_len = 0;
-    for (city1, city2) in path.iter().tuple_windows() {
-        path_len += distances.get(&(city1, city2))?;
+/// Finds a good route for graphs too big for `held_karp`, by starting
+/// with a greedy route and then reversing parts of it while that improves
+/// it. The result is usually close to the best route, but not always the
+/// best.
+fn two_opt(graph: &CityGraph, objective: Objective, tour: Tour) -> Option<Route> {
+    let mut cities = greedy_route(graph, objective)?;
+    let n = cities.len();
+
+    // Length of the road between two cities, where there being no city at
+    // one end (past the ends of an open route) costs nothing, and `None`
+    // means there is no road
+    let road = |from: Option<usize>, to: usize| from.map_or(Some(0), |from| graph.distance(from, to));
+
+    // A closed tour can start anywhere, so its first city stays put
+    let first = match tour {
+        Tour::Open => 0,
+        Tour::Closed => 1,
+    };
+
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in first..n {
+            for j in i + 1..n {
+                // Reversing cities[i..=j] swaps the roads at either end of
+                // that stretch, and for a closed tour the road from the
+                // last city wraps around to the first
+                let before = i.checked_sub(1).map(|k| cities[k]);
+                let after = match (j + 1 < n, tour) {
+                    (true, _) => Some(cities[j + 1]),
+                    (false, Tour::Closed) if i > 1 => Some(cities[0]),
+                    (false, _) => None,
+                };
+                if before.is_none() && after.is_none() {
+                    continue;
+                }
+
+                // Only make moves onto roads that exist, but moving off a
+                // missing road (the greedy route's way home) always helps
+                let new = match (road(before, cities[j]), road(after, cities[i])) {
+                    (Some(a), Some(b)) => a + b,
+                    _ => continue,
+                };
+                let old = road(before, cities[i]).zip(road(after, cities[j])).map(|(a, b)| a + b);
+                if old.is_none_or(|old| objective.is_better(new, old)) {
+                    cities[i..=j].reverse();
+                    improved = true;
+                }
+            }
+        }
+    }
+
+    let length = graph.route_length(&cities, tour)?;
+    Some(Route { cities, length })
+}
+
+/// Visits the nearest (or farthest) unvisited city each step, starting
+/// from city 0.
+fn greedy_route(graph: &CityGraph, objective: Objective) -> Option<Vec<usize>> {
+    let mut visited = vec![false; graph.len()];
+    let mut cities = vec![0];
+    visited[0] = true;
+    while cities.len() < graph.len() {
+        let last = *cities.last().unwrap();
+        let next = (0..graph.len())
+            .filter(|&city| !visited[city])
+            .filter_map(|city| Some((graph.distance(last, city)?, city)))
+            .reduce(|a, b| if objective.is_better(b.0, a.0) { b } else { a })?
+            .1;
+        visited[next] = true;
+        cities.push(next);
+    }
+    Some(cities)
+}
+
+#[test]
+fn test_two_opt() {
+    let graph = CityGraph::new(&parse_input(INPUT));
+    for objective in [Objective::Shortest, Objective::Longest] {
+        for tour in [Tour::Open, Tour::Closed] {
+            let exact = held_karp(&graph, objective, tour).unwrap();
+            let heuristic = two_opt(&graph, objective, tour).unwrap();
+            assert_eq!(graph.route_length(&heuristic.cities, tour), Some(heuristic.length));
+            assert!(!objective.is_better(heuristic.length, exact.length));
+        }
+    }
+
+    // The greedy route is A B C D, and the best fix is to swap A and B
+    let graph = CityGraph::new(&parse_input("A to B = 1\nA to C = 2\nA to D = 3\nB to C = 3\nB to D = 4\nC to D = 1"));
+    let route = two_opt(&graph, Objective::Shortest, Tour::Open).unwrap();
+    assert_eq!(route.length, 4);
+    assert_eq!(graph.display_route(&route).to_string(), "B -> A -> C -> D");
+
+    // The greedy tour A B C D has no road back to A
+    let graph = CityGraph::new(&parse_input("A to B = 1\nB to C = 1\nC to D = 1\nA to C = 5\nB to D = 5"));
+    assert_eq!(two_opt(&graph, Objective::Shortest, Tour::Closed).unwrap().length, 12);
+    assert_eq!(held_karp(&graph, Objective::Shortest, Tour::Closed).unwrap().length, 12);
+
+    // Cities on a line are easy to get right, and too many for held_karp
+    let mut input = String::new();
+    for a in 0..200u64 {
+        for b in a + 1..200 {
+            // Shuffle the positions so the greedy route isn't already best
+            let (x, y) = ((a * 7) % 200, (b * 7) % 200);
+            input.push_str(&format!("C{} to C{} = {}\n", x, y, x.abs_diff(y)));
+        }
     }
-    Some(path_len)
+    let graph = CityGraph::new(&parse_input(input.trim_end()));
+    assert_eq!(two_opt(&graph, Objective::Shortest, Tour::Open).unwrap().length, 199);
+    assert_eq!(two_opt(&graph, Objective::Shortest, Tour::Closed).unwrap().length, 2 * 199);
 }
 
 fn parse_input(input: &str) -> Vec<CityDistance> {
@@ -58,20 +58,133 @@
 // quantum entanglement would be chosen.
 //
 // What is the quantum entanglement of the first group of packages in the ideal configuration?
+//
+// For part 2, the packages need to be split into four groups instead
+// of three, adding the trunk to the compartments.
+
+use itertools::Itertools;
 
 fn main() {
-    println!("nothing yet");
+    // The package list isn't checked in, so pass a file with one weight
+    // per line on the command line. Without it we use the example.
+    let weights: Vec<u64> = match std::env::args().nth(1) {
+        Some(path) => std::fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("couldn't read {}: {}", path, err))
+            .lines()
+            .map(|line| line.trim().parse().expect("parse weight"))
+            .collect(),
+        None => _EXAMPLE.to_vec(),
+    };
+
+    for (part, groups) in [(1, 3), (2, 4)] {
+        let arrangement = ideal_arrangement(&weights, groups).expect("couldn't balance the sleigh");
+        println!("part {}: {}", part, quantum_entanglement(&arrangement[0]));
+        for (i, group) in arrangement.iter().enumerate() {
+            println!("  group {}: {}", i + 1, group.iter().join(" "));
+        }
+    }
+}
+
+fn quantum_entanglement(group: &[u64]) -> u64 {
+    group.iter().product()
+}
+
+/// Splits the packages into `groups` groups of equal weight, where the
+/// first group has as few packages as possible, and after that the
+/// smallest quantum entanglement. Returns `None` if the packages can't
+/// be balanced.
+fn ideal_arrangement(weights: &[u64], groups: usize) -> Option<Vec<Vec<u64>>> {
+    let total_weight: u64 = weights.iter().sum();
+    if groups == 0 || total_weight % groups as u64 != 0 {
+        return None;
+    }
+    let group_weight = total_weight / groups as u64;
+
+    // Heaviest first so the first group fills up in fewer packages
+    let weights: Vec<u64> = weights.iter().copied().sorted_unstable_by(|a, b| b.cmp(a)).collect();
+
+    for size in 1..=weights.len() {
+        let mut candidates: Vec<Vec<usize>> = (0..weights.len())
+            .combinations(size)
+            .filter(|indexes| indexes.iter().map(|&i| weights[i]).sum::<u64>() == group_weight)
+            .collect();
+        candidates.sort_by_cached_key(|indexes| indexes.iter().map(|&i| weights[i]).product::<u64>());
+
+        // The first group is only valid if the rest of the packages
+        // can still be split evenly
+        for indexes in candidates {
+            let first_group: Vec<u64> = indexes.iter().map(|&i| weights[i]).collect();
+            let rest: Vec<u64> = (0..weights.len())
+                .filter(|i| !indexes.contains(i))
+                .map(|i| weights[i])
+                .collect();
+
+            if let Some(other_groups) = partition(&rest, groups - 1, group_weight) {
+                return Some(std::iter::once(first_group).chain(other_groups).collect());
+            }
+        }
+    }
+
+    None
+}
+
+#[test]
+fn test_ideal_arrangement() {
+    let arrangement = ideal_arrangement(&_EXAMPLE, 3).expect("no arrangement");
+    assert_eq!(arrangement[0], vec![11, 9]);
+    assert_eq!(quantum_entanglement(&arrangement[0]), 99);
+    assert!(arrangement.iter().all(|group| group.iter().sum::<u64>() == 20));
+
+    let arrangement = ideal_arrangement(&_EXAMPLE, 4).expect("no arrangement");
+    assert_eq!(quantum_entanglement(&arrangement[0]), 44);
+    assert!(arrangement.iter().all(|group| group.iter().sum::<u64>() == 15));
+
+    assert_eq!(ideal_arrangement(&[1, 2, 4], 3), None);
 }
 
-// fn find_balanced_configurations(weights: Vec<u32>, groups: u32) {
-//     let total_weight: u32 = weights.iter().sum();
-//     assert_eq!(total_weight.rem_euclid(groups), 0);
-//     let group_weight = total_weight / groups;
-// }
+/// Splits weights (sorted heaviest first) into `groups` groups that
+/// each weigh `group_weight`, if possible.
+fn partition(weights: &[u64], groups: usize, group_weight: u64) -> Option<Vec<Vec<u64>>> {
+    let mut loads = vec![0; groups];
+    let mut assignment = vec![0; weights.len()];
+    if !assign_packages(weights, group_weight, 0, &mut loads, &mut assignment) {
+        return None;
+    }
 
-// fn find_balanced_configurations_inner(weights: &Vec<u32>, group_weight: u32, this_group: Vec<u32>, remaining: Vec<u32>) {
-//     let mut this_group = this_group.clone();
-//     let mut remaining = remaining.clone();
+    let mut result = vec![Vec::new(); groups];
+    for (weight, group) in weights.iter().zip(assignment) {
+        result[group].push(*weight);
+    }
+    Some(result)
+}
+
+fn assign_packages(
+    weights: &[u64],
+    group_weight: u64,
+    next: usize,
+    loads: &mut Vec<u64>,
+    assignment: &mut Vec<usize>,
+) -> bool {
+    if next == weights.len() {
+        return loads.iter().all(|&load| load == group_weight);
+    }
+
+    for group in 0..loads.len() {
+        // Groups with the same load are interchangeable, so only try
+        // the first of them
+        if loads[..group].contains(&loads[group]) || loads[group] + weights[next] > group_weight {
+            continue;
+        }
+
+        loads[group] += weights[next];
+        assignment[next] = group;
+        if assign_packages(weights, group_weight, next + 1, loads, assignment) {
+            return true;
+        }
+        loads[group] -= weights[next];
+    }
+
+    false
+}
 
-//     let current_weight: u32 = this_group.iter().sum();
-// }
+const _EXAMPLE: [u64; 10] = [1, 2, 3, 4, 5, 7, 8, 9, 10, 11];
@@ -1,17 +1,39 @@
 use core::cmp::{Ordering, Reverse};
-use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 
 fn main() {
-    let puzzle = parse_input(_EXAMPLE2);
-    println!("{:?}", key_distances(&puzzle, puzzle.start.clone(), HashSet::new()));
-    println!("{:?}", key_distances(&puzzle, puzzle.start.clone(), vec!['a', 'b', 'c', 'd'].into_iter().collect()));
+    // The maze input isn't checked in, so pass its path on the command line
+    let path = std::env::args().nth(1).expect("usage: 2019-18-many-worlds <maze input path>");
+    let input = std::fs::read_to_string(&path).unwrap_or_else(|err| panic!("couldn't read {}: {}", path, err));
+
+    let puzzle = parse_input(input.trim_end());
+    println!("part1: {}", min_puzzle_distance(&puzzle).expect("no way to collect all keys"));
+
+    let mut puzzle = parse_input(input.trim_end());
+    split_into_quadrants(&mut puzzle);
+    println!("part2: {}", min_puzzle_distance(&puzzle).expect("no way to collect all keys"));
+}
+
+/// Bitmask of keys, where bit 0 is 'a'
+type KeySet = u32;
+
+fn key_bit(c: char) -> KeySet {
+    1 << (c as u8 - b'a')
+}
+
+/// Places robots stand while collecting keys. Robots start at their
+/// start position and only ever move to keys after that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Node {
+    Start(usize),
+    Key(char),
 }
 
 #[derive(Debug, PartialEq, Eq)]
 struct PuzzleState {
     distance: usize,
-    position: Position,
-    keys: HashSet<char>,
+    positions: Vec<Node>,
+    keys: KeySet,
 }
 
 impl PartialOrd for PuzzleState {
@@ -26,96 +48,348 @@ impl Ord for PuzzleState {
     }
 }
 
-fn min_puzzle_distance(puzzle: &Puzzle) -> u32 {
+/// Shortest number of steps for the robots to collect every key. Each
+/// step of the search moves a single robot to a key it hasn't
+/// collected yet, which is only allowed if we have the keys for every
+/// door along the way.
+fn min_puzzle_distance(puzzle: &Puzzle) -> Option<usize> {
+    let all_keys = puzzle.keys.iter().fold(0, |acc, &c| acc | key_bit(c));
+
+    // Paths from every node to every key
+    let mut paths: HashMap<Node, HashMap<char, Vec<KeyPath>>> = HashMap::new();
+    for (i, start) in puzzle.starts.iter().enumerate() {
+        paths.insert(Node::Start(i), key_distances(puzzle, start));
+    }
+    for (&c, position) in puzzle.key_positions.iter() {
+        paths.insert(Node::Key(c), key_distances(puzzle, position));
+    }
+
     let start = PuzzleState {
         distance: 0,
-        position: puzzle.start.clone(),
-        keys: HashSet::new(),
+        positions: (0..puzzle.starts.len()).map(Node::Start).collect(),
+        keys: 0,
     };
 
+    let mut best: HashMap<(Vec<Node>, KeySet), usize> = HashMap::new();
     let mut to_visit = BinaryHeap::new();
     to_visit.push(Reverse(start));
 
-    while let Some(state) = to_visit.pop() {}
+    while let Some(Reverse(state)) = to_visit.pop() {
+        if state.keys == all_keys {
+            return Some(state.distance);
+        }
+
+        if let Some(&distance) = best.get(&(state.positions.clone(), state.keys)) {
+            if distance < state.distance {
+                continue;
+            }
+        }
+
+        for (robot, node) in state.positions.iter().enumerate() {
+            for (&c, key_paths) in paths[node].iter() {
+                if state.keys & key_bit(c) != 0 {
+                    continue;
+                }
+
+                // Doors without a key in the maze never block us. If we'd
+                // walk over a key we don't have yet, then picking that one
+                // up first is just as short, so we skip that path.
+                let shortest = key_paths
+                    .iter()
+                    .filter(|path| path.required_keys & all_keys & !state.keys == 0)
+                    .filter(|path| path.keys_on_path & !state.keys == 0)
+                    .map(|path| path.distance)
+                    .min();
+                let path_distance = match shortest {
+                    Some(distance) => distance,
+                    None => continue,
+                };
+
+                let mut positions = state.positions.clone();
+                positions[robot] = Node::Key(c);
+                let keys = state.keys | key_bit(c);
+                let distance = state.distance + path_distance;
 
-    0
+                let entry = best.entry((positions.clone(), keys)).or_insert(usize::MAX);
+                if distance < *entry {
+                    *entry = distance;
+                    to_visit.push(Reverse(PuzzleState { distance, positions, keys }));
+                }
+            }
+        }
+    }
+
+    None
+}
+
+#[test]
+fn test_min_puzzle_distance() {
+    assert_eq!(min_puzzle_distance(&parse_input(_EXAMPLE1)), Some(8));
+    assert_eq!(min_puzzle_distance(&parse_input(_EXAMPLE2)), Some(86));
+    assert_eq!(min_puzzle_distance(&parse_input(_EXAMPLE3)), Some(132));
+    assert_eq!(min_puzzle_distance(&parse_input(_EXAMPLE4)), Some(136));
+    assert_eq!(min_puzzle_distance(&parse_input(_EXAMPLE5)), Some(81));
+
+    // The short way to b goes through door C, but c is behind door B, so
+    // we have to go the long way around the loop to b first.
+    let looped = "#########
+#b.C.@.B#
+#.###.#c#
+#.....###
+#########";
+    assert_eq!(min_puzzle_distance(&parse_input(looped)), Some(19));
+
+    // Full sized mazes with loops and doors, checked against a search over
+    // every (position, keys) state. Seed 2's doors make it impossible
+    // to collect every key.
+    for (seed, expected) in [(1, Some(1230)), (2, None), (4, Some(1076)), (5, Some(1018))] {
+        let puzzle = parse_input(&generate_maze(seed));
+        assert_eq!(brute_force_distance(&puzzle), expected, "seed {}", seed);
+        assert_eq!(min_puzzle_distance(&puzzle), expected, "seed {}", seed);
+    }
+}
+
+/// Generates an 81x81 maze like the real input: a maze carved out of
+/// walls with the start in an open 3x3 center, plus some extra walls
+/// knocked out to make loops, and keys and doors a through h scattered
+/// around.
+#[cfg(test)]
+fn generate_maze(seed: u64) -> String {
+    const SIZE: usize = 81;
+    let mut rng = seed;
+    let mut random = |n: usize| {
+        // Knuth's MMIX linear congruential generator
+        rng = rng.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (rng >> 33) as usize % n
+    };
+
+    // Rooms are at odd coordinates, with walls between them
+    let mut grid = vec![vec!['#'; SIZE]; SIZE];
+    let mut stack = vec![(1, 1)];
+    grid[1][1] = '.';
+    while let Some(&(x, y)) = stack.last() {
+        let unvisited: Vec<(usize, usize)> = [(0, -2), (0, 2), (-2, 0), (2, 0)]
+            .iter()
+            .map(|(dx, dy)| ((x as isize + dx) as usize, (y as isize + dy) as usize))
+            .filter(|&(nx, ny)| nx > 0 && ny > 0 && nx < SIZE - 1 && ny < SIZE - 1 && grid[ny][nx] == '#')
+            .collect();
+        if unvisited.is_empty() {
+            stack.pop();
+            continue;
+        }
+        let (nx, ny) = unvisited[random(unvisited.len())];
+        grid[(y + ny) / 2][(x + nx) / 2] = '.';
+        grid[ny][nx] = '.';
+        stack.push((nx, ny));
+    }
+
+    for _ in 0..100 {
+        let (x, y) = (random(SIZE - 2) + 1, random(SIZE - 2) + 1);
+        if (x % 2 == 1) != (y % 2 == 1) {
+            grid[y][x] = '.';
+        }
+    }
+
+    for row in grid.iter_mut().take(SIZE / 2 + 2).skip(SIZE / 2 - 1) {
+        for tile in row.iter_mut().take(SIZE / 2 + 2).skip(SIZE / 2 - 1) {
+            *tile = '.';
+        }
+    }
+    grid[SIZE / 2][SIZE / 2] = '@';
+
+    for c in "abcdefghABCDEFGH".chars() {
+        loop {
+            let (x, y) = (random(SIZE / 2) * 2 + 1, random(SIZE / 2) * 2 + 1);
+            if grid[y][x] == '.' && (x.abs_diff(SIZE / 2) > 1 || y.abs_diff(SIZE / 2) > 1) {
+                grid[y][x] = c;
+                break;
+            }
+        }
+    }
+
+    grid.iter().map(|row| row.iter().collect::<String>()).collect::<Vec<_>>().join("\n")
+}
+
+/// Finds the fewest steps to collect every key with a breadth first
+/// search over (position, keys collected) for a single robot.
+#[cfg(test)]
+fn brute_force_distance(puzzle: &Puzzle) -> Option<usize> {
+    let all_keys = puzzle.keys.iter().fold(0, |keys, &c| keys | key_bit(c));
+    let start = (puzzle.starts[0].clone(), 0);
+    let mut seen: HashSet<(Position, KeySet)> = HashSet::from([start.clone()]);
+    let mut pending = VecDeque::from([(start, 0)]);
+
+    while let Some(((position, keys), distance)) = pending.pop_front() {
+        if keys == all_keys {
+            return Some(distance);
+        }
+        let state = KeyDistanceState { position, distance, required_keys: 0, keys_on_path: 0 };
+        for next in key_distance_next_4(puzzle, &state) {
+            let next_keys = match puzzle.tiles[next.y][next.x] {
+                Tile::Wall => continue,
+                Tile::Door(c) if keys & key_bit(c) == 0 => continue,
+                Tile::Key(c) => keys | key_bit(c),
+                _ => keys,
+            };
+            if seen.insert((next.clone(), next_keys)) {
+                pending.push_back(((next, next_keys), distance + 1));
+            }
+        }
+    }
+
+    None
+}
+
+#[test]
+fn test_min_puzzle_distance_four_robots() {
+    let mut puzzle = parse_input(_PART2_EXAMPLE1);
+    split_into_quadrants(&mut puzzle);
+    assert_eq!(min_puzzle_distance(&puzzle), Some(8));
+
+    assert_eq!(min_puzzle_distance(&parse_input(_PART2_EXAMPLE2)), Some(24));
+    assert_eq!(min_puzzle_distance(&parse_input(_PART2_EXAMPLE3)), Some(72));
+}
+
+/// In part 2 the area around the single start is replaced with walls
+/// and four robots, one in each quadrant:
+///
+/// ```text
+/// ...      @#@
+/// .@.  =>  ###
+/// ...      @#@
+/// ```
+///
+/// Mazes that already have four starts are left alone.
+fn split_into_quadrants(puzzle: &mut Puzzle) {
+    if puzzle.starts.len() == 4 {
+        return;
+    }
+    assert_eq!(puzzle.starts.len(), 1, "can only split a maze with one start");
+
+    let Position { x, y } = puzzle.starts[0].clone();
+    for (wall_x, wall_y) in [(x, y), (x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+        puzzle.tiles[wall_y][wall_x] = Tile::Wall;
+    }
+
+    puzzle.starts = vec![
+        Position { x: x - 1, y: y - 1 },
+        Position { x: x + 1, y: y - 1 },
+        Position { x: x - 1, y: y + 1 },
+        Position { x: x + 1, y: y + 1 },
+    ];
+    for start in puzzle.starts.iter() {
+        puzzle.tiles[start.y][start.x] = Tile::Empty;
+    }
+}
+
+/// A path to a key, the keys we need to open the doors along the way,
+/// and the other keys we walk over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct KeyPath {
+    distance: usize,
+    required_keys: KeySet,
+    keys_on_path: KeySet,
 }
 
 #[derive(Debug, PartialEq, Eq)]
 struct KeyDistanceState {
     position: Position,
     distance: usize,
-    seen_keys: HashSet<char>,
-}
-
-/// key_distances computes the distance from the current `location` to all of
-/// the keys in the puzzle. The set of `current_keys` is used to determine which
-/// doors we are allowed to pass through.
-fn key_distances(
-    puzzle: &Puzzle,
-    location: Position,
-    current_keys: HashSet<char>,
-) -> HashMap<char, usize> {
-    let mut explored: HashSet<Position> = HashSet::new();
-    let mut pending: Vec<KeyDistanceState> = Vec::new();
-    pending.push(KeyDistanceState {
-        position: location,
+    required_keys: KeySet,
+    keys_on_path: KeySet,
+}
+
+/// key_distances computes paths from the current `location` to all of
+/// the reachable keys in the puzzle, ignoring doors but recording which
+/// keys they require. Mazes can have loops, so the shortest path to a key
+/// might go through doors that a longer path avoids. The search state
+/// includes the doors and keys passed, and every path to a key is kept
+/// unless another is at least as short and passes a subset of the same
+/// doors and keys.
+fn key_distances(puzzle: &Puzzle, location: &Position) -> HashMap<char, Vec<KeyPath>> {
+    // Door and key sets we've already been at each position with. States
+    // are visited in order of distance, so getting somewhere again with a
+    // superset of one of these can't be better.
+    let mut explored: HashMap<Position, Vec<(KeySet, KeySet)>> = HashMap::new();
+    let mut pending: VecDeque<KeyDistanceState> = VecDeque::new();
+    pending.push_back(KeyDistanceState {
+        position: location.clone(),
         distance: 0,
-        seen_keys: current_keys,
+        required_keys: 0,
+        keys_on_path: 0,
     });
-    let mut distances: HashMap<char, usize> = HashMap::new();
-
-    while let Some(state) = pending.pop() {
-        // Check if we've been here before
-        if explored.contains(&state.position) {
-            continue;
-        }
-        explored.insert(state.position.clone());
+    explored.insert(location.clone(), vec![(0, 0)]);
+    let mut distances: HashMap<char, Vec<KeyPath>> = HashMap::new();
 
-        // Check for next steps
+    while let Some(state) = pending.pop_front() {
         for position in key_distance_next_4(puzzle, &state) {
             let distance = state.distance + 1;
-            // TODO: This clone sucks and is probably bad for performance
-            let seen_keys = state.seen_keys.clone();
+            let mut required_keys = state.required_keys;
+            let mut keys_on_path = state.keys_on_path;
 
+            let mut found_key = None;
             match puzzle.tiles[position.y][position.x] {
                 Tile::Wall => continue,
-                Tile::Key(c) => {
-                    if let Some(&d) = distances.get(&c) {
-                        distances.insert(c, std::cmp::min(d, distance));
-                    } else {
-                        distances.insert(c, distance);
-                    }
+                Tile::Key(c) => found_key = Some(c),
+                Tile::Door(c) => required_keys |= key_bit(c),
+                Tile::Empty => {}
+            }
 
-                    let mut new_seen_keys = seen_keys.clone();
-                    new_seen_keys.insert(c);
-                    pending.push(KeyDistanceState {
-                        position,
-                        distance,
-                        seen_keys: new_seen_keys,
-                    });
-                }
-                Tile::Door(c) => {
-                    if seen_keys.contains(&c) {
-                        pending.push(KeyDistanceState {
-                            position,
-                            distance,
-                            seen_keys,
-                        });
-                    }
-                }
-                Tile::Empty => pending.push(KeyDistanceState {
-                    position,
-                    distance,
-                    seen_keys,
-                }),
+            let seen = explored.entry(position.clone()).or_default();
+            let dominated = |&(required, on_path): &(KeySet, KeySet)| {
+                required & !required_keys == 0 && on_path & !keys_on_path == 0
+            };
+            if seen.iter().any(dominated) {
+                continue;
+            }
+            seen.push((required_keys, keys_on_path));
+
+            if let Some(c) = found_key {
+                let path = KeyPath { distance, required_keys, keys_on_path };
+                let paths = distances.entry(c).or_default();
+                paths.retain(|other| {
+                    !(path.distance <= other.distance
+                        && path.required_keys & !other.required_keys == 0
+                        && path.keys_on_path & !other.keys_on_path == 0)
+                });
+                paths.push(path);
+                keys_on_path |= key_bit(c);
             }
+
+            pending.push_back(KeyDistanceState {
+                position,
+                distance,
+                required_keys,
+                keys_on_path,
+            });
         }
     }
 
     distances
 }
 
+#[test]
+fn test_key_distances() {
+    let puzzle = parse_input(
+        "#########
+#b.C.@.B#
+#.###.#c#
+#.....###
+#########",
+    );
+    let paths = key_distances(&puzzle, &puzzle.starts[0]);
+    let mut to_b = paths[&'b'].clone();
+    to_b.sort_by_key(|path| path.distance);
+    assert_eq!(
+        to_b,
+        vec![
+            KeyPath { distance: 4, required_keys: key_bit('c'), keys_on_path: 0 },
+            KeyPath { distance: 8, required_keys: 0, keys_on_path: 0 },
+        ]
+    );
+    assert_eq!(paths[&'c'], vec![KeyPath { distance: 3, required_keys: key_bit('b'), keys_on_path: 0 }]);
+}
+
 fn key_distance_next_4(puzzle: &Puzzle, state: &KeyDistanceState) -> Vec<Position> {
     let mut positions = Vec::new();
     if state.position.x > 0 {
@@ -152,8 +426,9 @@ fn key_distance_next_4(puzzle: &Puzzle, state: &KeyDistanceState) -> Vec<Positio
 struct Puzzle {
     height: usize,
     width: usize,
-    start: Position,
+    starts: Vec<Position>,
     keys: HashSet<char>,
+    key_positions: HashMap<char, Position>,
     tiles: Vec<Vec<Tile>>,
 }
 
@@ -173,8 +448,9 @@ enum Tile {
 
 fn parse_input(input: &str) -> Puzzle {
     let lines = input.lines().collect::<Vec<&str>>();
-    let mut start: Option<Position> = None;
+    let mut starts = Vec::new();
     let mut keys = HashSet::new();
+    let mut key_positions = HashMap::new();
 
     let tiles = lines
         .iter()
@@ -184,13 +460,14 @@ fn parse_input(input: &str) -> Puzzle {
                 .enumerate()
                 .map(|(x, c)| match c {
                     '@' => {
-                        start = Some(Position { x, y });
+                        starts.push(Position { x, y });
                         Tile::Empty
                     }
                     '.' => Tile::Empty,
                     '#' => Tile::Wall,
                     'a'..='z' => {
                         keys.insert(c);
+                        key_positions.insert(c, Position { x, y });
                         Tile::Key(c)
                     }
                     'A'..='Z' => Tile::Door(c.to_ascii_lowercase()),
@@ -200,11 +477,14 @@ fn parse_input(input: &str) -> Puzzle {
         })
         .collect();
 
+    assert!(!starts.is_empty(), "couldn't find start tile");
+
     Puzzle {
         height: lines.len(),
         width: lines[0].len(),
-        start: start.expect("couldn't find start tile"),
+        starts,
         keys,
+        key_positions,
         tiles,
     }
 }
@@ -218,3 +498,52 @@ const _EXAMPLE2: &str = "########################
 ######################.#
 #d.....................#
 ########################";
+
+const _EXAMPLE3: &str = "########################
+#...............b.C.D.f#
+#.######################
+#.....@.a.B.c.d.A.e.F.g#
+########################";
+
+const _EXAMPLE4: &str = "#################
+#i.G..c...e..H.p#
+########.########
+#j.A..b...f..D.o#
+########@########
+#k.E..a...g..B.n#
+########.########
+#l.F..d...h..C.m#
+#################";
+
+const _EXAMPLE5: &str = "########################
+#@..............ac.GI.b#
+###d#e#f################
+###A#B#C################
+###g#h#i################
+########################";
+
+const _PART2_EXAMPLE1: &str = "#######
+#a.#Cd#
+##...##
+##.@.##
+##...##
+#cB#Ab#
+#######";
+
+const _PART2_EXAMPLE2: &str = "###############
+#d.ABC.#.....a#
+######@#@######
+###############
+######@#@######
+#b.....#.....c#
+###############";
+
+const _PART2_EXAMPLE3: &str = "#############
+#g#f.D#..h#l#
+#F###e#E###.#
+#dCba@#@BcIJ#
+#############
+#nK.L@#@G...#
+#M###N#H###.#
+#o#m..#i#jk.#
+#############";
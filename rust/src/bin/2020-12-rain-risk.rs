@@ -1,54 +1,171 @@
 fn main() {
     let instructions = parse_input(INPUT);
-    let (part1_x, part1_y) = part1_simulate(&instructions);
-    println!("part1: {}", part1_x.abs() + part1_y.abs());
+
+    let part1_route = navigate(&instructions, NavigationMode::MoveShip);
+    println!("part1: {}", manhattan_distance(&part1_route));
+
+    let part2_route = navigate(&instructions, NavigationMode::MoveWaypoint);
+    println!("part2: {}", manhattan_distance(&part2_route));
+
+    // Pass a path on the command line to plot both routes as an SVG
+    if let Some(path) = std::env::args().nth(1) {
+        let svg = routes_svg(&[&part1_route, &part2_route]);
+        std::fs::write(&path, svg).unwrap_or_else(|err| panic!("couldn't write {}: {}", path, err));
+    }
 }
 
-fn part1_simulate(instructions: &[Instruction]) -> (i32, i32) {
-    let mut x = 0;
-    let mut y = 0;
-    let mut heading: i32 = 0; // East is zero, counter-clockwise is positive (north is 90)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Vector {
+    x: i32, // East is positive
+    y: i32, // North is positive
+}
 
-    for instruction in instructions {
+impl Vector {
+    fn add_scaled(self, other: Vector, scale: i32) -> Vector {
+        Vector {
+            x: self.x + other.x * scale,
+            y: self.y + other.y * scale,
+        }
+    }
+
+    /// Rotates counter-clockwise around the origin by a multiple of 90
+    /// degrees. Negative degrees rotate clockwise.
+    fn rotate(self, degrees: i32) -> Vector {
+        assert_eq!(degrees % 90, 0, "can only rotate by multiples of 90 degrees, got {}", degrees);
+        match degrees.rem_euclid(360) {
+            0 => self,
+            90 => Vector { x: -self.y, y: self.x },
+            180 => Vector { x: -self.x, y: -self.y },
+            270 => Vector { x: self.y, y: -self.x },
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// The two parts interpret N/S/E/W differently. Everything else is the
+/// same: L/R rotate the waypoint and F moves the ship toward the
+/// waypoint. In part 1 the waypoint is just the ship's heading.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NavigationMode {
+    MoveShip,
+    MoveWaypoint,
+}
+
+#[derive(Debug)]
+struct Ship {
+    mode: NavigationMode,
+    position: Vector,
+    /// Relative to the ship
+    waypoint: Vector,
+}
+
+impl Ship {
+    fn new(mode: NavigationMode) -> Self {
+        let waypoint = match mode {
+            NavigationMode::MoveShip => Vector { x: 1, y: 0 },
+            NavigationMode::MoveWaypoint => Vector { x: 10, y: 1 },
+        };
+        Ship { mode, position: Vector { x: 0, y: 0 }, waypoint }
+    }
+
+    fn apply(&mut self, instruction: &Instruction) {
         match instruction {
-            Instruction::North(num) => {
-                y += *num as i32;
-            }
-            Instruction::South(num) => {
-                y -= *num as i32;
-            }
-            Instruction::East(num) => {
-                x += *num as i32;
-            }
-            Instruction::West(num) => {
-                x -= *num as i32;
-            }
-            Instruction::Left(num) => {
-                heading = (heading + *num as i32).rem_euclid(360);
-            }
-            Instruction::Right(num) => {
-                heading = (heading - *num as i32).rem_euclid(360);
-            }
-            Instruction::Forward(num) => match heading {
-                0 => {
-                    x += *num as i32;
-                }
-                90 => {
-                    y += *num as i32;
-                }
-                180 => {
-                    x -= *num as i32;
-                }
-                270 => {
-                    y -= *num as i32;
-                }
-                _ => panic!("invalid heading {}", heading),
-            },
+            Instruction::North(num) => self.shift(Vector { x: 0, y: 1 }, *num),
+            Instruction::South(num) => self.shift(Vector { x: 0, y: -1 }, *num),
+            Instruction::East(num) => self.shift(Vector { x: 1, y: 0 }, *num),
+            Instruction::West(num) => self.shift(Vector { x: -1, y: 0 }, *num),
+            Instruction::Left(num) => self.waypoint = self.waypoint.rotate(*num as i32),
+            Instruction::Right(num) => self.waypoint = self.waypoint.rotate(-(*num as i32)),
+            Instruction::Forward(num) => self.position = self.position.add_scaled(self.waypoint, *num as i32),
         }
-        //println!("inst: {:?}, x: {}, y: {}, heading: {}", instruction, x, y, heading);
     }
 
-    (x, y)
+    fn shift(&mut self, direction: Vector, num: u32) {
+        match self.mode {
+            NavigationMode::MoveShip => self.position = self.position.add_scaled(direction, num as i32),
+            NavigationMode::MoveWaypoint => self.waypoint = self.waypoint.add_scaled(direction, num as i32),
+        }
+    }
+}
+
+/// Returns every position the ship visits, starting at the origin.
+fn navigate(instructions: &[Instruction], mode: NavigationMode) -> Vec<Vector> {
+    let mut ship = Ship::new(mode);
+    let mut route = vec![ship.position];
+    for instruction in instructions {
+        ship.apply(instruction);
+        if route.last() != Some(&ship.position) {
+            route.push(ship.position);
+        }
+    }
+    route
+}
+
+fn manhattan_distance(route: &[Vector]) -> i32 {
+    let end = route.last().expect("empty route");
+    end.x.abs() + end.y.abs()
+}
+
+#[test]
+fn test_navigate() {
+    let instructions = parse_input(_EXAMPLE);
+
+    let route = navigate(&instructions, NavigationMode::MoveShip);
+    assert_eq!(
+        route,
+        vec![
+            Vector { x: 0, y: 0 },
+            Vector { x: 10, y: 0 },
+            Vector { x: 10, y: 3 },
+            Vector { x: 17, y: 3 },
+            Vector { x: 17, y: -8 },
+        ],
+    );
+    assert_eq!(manhattan_distance(&route), 25);
+
+    let route = navigate(&instructions, NavigationMode::MoveWaypoint);
+    assert_eq!(route.last(), Some(&Vector { x: 214, y: -72 }));
+    assert_eq!(manhattan_distance(&route), 286);
+}
+
+const ROUTE_COLORS: [&str; 4] = ["steelblue", "firebrick", "seagreen", "darkorange"];
+
+/// Plots routes as SVG polylines. SVG's y axis points down, so we flip
+/// it to keep north up.
+fn routes_svg(routes: &[&[Vector]]) -> String {
+    let all_points = routes.iter().flat_map(|route| route.iter());
+    let min_x = all_points.clone().map(|p| p.x).min().unwrap_or(0);
+    let max_x = all_points.clone().map(|p| p.x).max().unwrap_or(0);
+    let min_y = all_points.clone().map(|p| p.y).min().unwrap_or(0);
+    let max_y = all_points.map(|p| p.y).max().unwrap_or(0);
+
+    let width = (max_x - min_x).max(1);
+    let height = (max_y - min_y).max(1);
+    let stroke_width = (width.max(height) / 500).max(1);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n",
+        min_x, -max_y, width, height
+    );
+    for (i, route) in routes.iter().enumerate() {
+        let points: Vec<String> = route.iter().map(|p| format!("{},{}", p.x, -p.y)).collect();
+        svg.push_str(&format!(
+            "  <polyline fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" points=\"{}\" />\n",
+            ROUTE_COLORS[i % ROUTE_COLORS.len()],
+            stroke_width,
+            points.join(" "),
+        ));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[test]
+fn test_routes_svg() {
+    let route = navigate(&parse_input(_EXAMPLE), NavigationMode::MoveShip);
+    let svg = routes_svg(&[&route]);
+    assert!(svg.contains("viewBox=\"0 -3 17 11\""));
+    assert!(svg.contains("points=\"0,0 10,0 10,-3 17,-3 17,8\""));
 }
 
 #[derive(Debug)]
@@ -8,7 +8,7 @@ fn main() {
     println!("part1: {}", part1_mem.values().sum::<u64>());
 
     let part2_mem = apply_instructions_part2(&input);
-    println!("part2: {}", part2_mem.values().sum::<u64>());
+    println!("part2: {}", part2_mem.sum());
 }
 
 fn apply_instructions_part1(instructions: &[Instruction]) -> HashMap<usize, u64> {
@@ -51,93 +51,178 @@ fn test_apply_value_mask() {
     assert_eq!(apply_value_mask(&mask, 0), 64);
 }
 
-fn apply_instructions_part2(instructions: &[Instruction]) -> HashMap<usize, u64> {
+fn apply_instructions_part2(instructions: &[Instruction]) -> FloatingMemory {
     let mut mask = vec![None; 36];
-    let mut mem = HashMap::new();
+    let mut mem = FloatingMemory::default();
     for instruction in instructions {
         match instruction {
             Instruction::Mask(m) => {
                 mask = m.to_vec();
             },
             Instruction::Memset { index, value } => {
-                let mems = apply_address_mask(&mask, *index);
-                for index in mems {
-                    mem.insert(index, *value);
-                }
+                mem.write(address_pattern(&mask, *index), *value);
             },
         }
     }
     mem
 }
 
+/// A set of addresses where each bit is either fixed or floating.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AddressPattern {
+    // Value of the fixed bits. Floating bits are always zero here.
+    ones: u64,
+    floating: u64,
+}
 
-fn apply_address_mask(mask: &[Option<bool>], address: usize) -> Vec<usize> {
-    // First create the new mask
-    let mut new_mask: Vec<Option<bool>> = Vec::new();
-    for (i, mask_bit) in mask.iter().enumerate() {
-        match mask_bit {
-            None => {
-                // If the bitmask bit is X, the corresponding memory
-                // address bit is floating.
-                new_mask.push(None);
-            },
-            Some(true) => {
-                // If the bitmask bit is 1, the corresponding memory
-                // address bit is overwritten with 1.
-                new_mask.push(Some(true));
-            },
-            Some(false) => {
-                // If the bitmask bit is 0, the corresponding memory
-                // address bit is unchanged.
-                let shift = mask.len() - i - 1;
-                new_mask.push(Some((address >> shift & 1) != 0));
-            },
+impl AddressPattern {
+    fn len(&self) -> u64 {
+        1 << self.floating.count_ones()
+    }
+
+    fn intersection(&self, other: &AddressPattern) -> Option<AddressPattern> {
+        let both_fixed = !self.floating & !other.floating;
+        if (self.ones ^ other.ones) & both_fixed != 0 {
+            return None;
         }
+        let floating = self.floating & other.floating;
+        Some(AddressPattern { ones: (self.ones | other.ones) & !floating, floating })
     }
 
-    // Then apply the mask to the address
-    let mut result = vec![0];
-    for mask_bit in new_mask {
-        match mask_bit {
-            Some(true) => {
-                for x in &mut result {
-                    *x = *x << 1 | 1;
-                }
-            },
-            Some(false) => {
-                for x in &mut result {
-                    *x = *x << 1;
-                }
-            },
-            None => {
-                let mut new_result = vec![];
-                for x in &mut result {
-                    new_result.push(*x << 1 | 1);
-                }
-                for x in &mut result {
-                    new_result.push(*x << 1);
-                }
-                result = new_result;
-            },
+    /// Returns disjoint patterns covering the addresses in `self` but not
+    /// in `other`.
+    fn difference(&self, other: &AddressPattern) -> Vec<AddressPattern> {
+        if self.intersection(other).is_none() {
+            return vec![*self];
         }
 
+        // Pin down each bit that floats here but is fixed in other, one at
+        // a time. Setting it to the opposite of other's value splits off a
+        // piece that can't overlap, and the rest keeps other's value.
+        let mut pieces = Vec::new();
+        let mut rest = *self;
+        let mut splits = self.floating & !other.floating;
+        while splits != 0 {
+            let bit = splits & splits.wrapping_neg();
+            splits &= !bit;
+
+            rest.floating &= !bit;
+            pieces.push(AddressPattern { ones: rest.ones | (!other.ones & bit), floating: rest.floating });
+            rest.ones |= other.ones & bit;
+        }
+        pieces
     }
-    result
 }
 
 #[test]
-fn test_apply_address_mask() {
+fn test_address_pattern() {
+    let a = AddressPattern { ones: 0b0010, floating: 0b1001 };
+    let b = AddressPattern { ones: 0b0100, floating: 0b0011 };
+    assert_eq!(a.len(), 4);
+    assert_eq!(a.intersection(&b), None);
+
+    let c = AddressPattern { ones: 0b1000, floating: 0b0111 };
+    assert_eq!(a.intersection(&c), Some(AddressPattern { ones: 0b1010, floating: 0b0001 }));
+
+    let pieces = a.difference(&c);
+    let mut got: Vec<u64> = pieces.iter().flat_map(pattern_addresses).collect();
+    got.sort_unstable();
+    assert_eq!(got, vec![0b0010, 0b0011]);
+    assert_eq!(b.difference(&a), vec![b]);
+    assert_eq!(c.difference(&c), vec![]);
+}
+
+/// Lists every address in a pattern, for checking against in tests.
+#[cfg(test)]
+fn pattern_addresses(pattern: &AddressPattern) -> Vec<u64> {
+    let mut addresses = vec![pattern.ones];
+    for offset in 0..64 {
+        if pattern.floating >> offset & 1 == 1 {
+            addresses = addresses.iter().flat_map(|a| [*a, *a | 1 << offset]).collect();
+        }
+    }
+    addresses
+}
+
+fn address_pattern(mask: &[Option<bool>], address: usize) -> AddressPattern {
+    let mut ones = address as u64;
+    let mut floating = 0;
+    for (offset, mask_bit) in mask.iter().rev().enumerate() {
+        match mask_bit {
+            // If the bitmask bit is 0, the corresponding memory address
+            // bit is unchanged.
+            Some(false) => {},
+            // If the bitmask bit is 1, the corresponding memory address
+            // bit is overwritten with 1.
+            Some(true) => ones |= 1 << offset,
+            // If the bitmask bit is X, the corresponding memory address
+            // bit is floating.
+            None => floating |= 1 << offset,
+        }
+    }
+    AddressPattern { ones: ones & !floating, floating }
+}
+
+#[test]
+fn test_address_pattern_addresses() {
     let mask = vec![None, Some(true), Some(false), Some(false), Some(true), None];
-    let mut got = apply_address_mask(&mask, 42);
-    got.sort();
+    let mut got = pattern_addresses(&address_pattern(&mask, 42));
+    got.sort_unstable();
     assert_eq!(got, vec![26, 27, 58, 59]);
 
     let mask = vec![Some(true), None, Some(false), None, None];
-    let mut got = apply_address_mask(&mask, 26);
-    got.sort();
+    let mut got = pattern_addresses(&address_pattern(&mask, 26));
+    got.sort_unstable();
     assert_eq!(got, vec![16, 17, 18, 19, 24, 25, 26, 27]);
 }
 
+/// Memory stored as disjoint address patterns that each hold one value.
+#[derive(Debug, Default)]
+struct FloatingMemory {
+    regions: Vec<(AddressPattern, u64)>,
+}
+
+impl FloatingMemory {
+    fn write(&mut self, pattern: AddressPattern, value: u64) {
+        // Carve the new pattern out of everything it overwrites
+        let mut regions = Vec::with_capacity(self.regions.len() + 1);
+        for (region, region_value) in &self.regions {
+            regions.extend(region.difference(&pattern).into_iter().map(|piece| (piece, *region_value)));
+        }
+        regions.push((pattern, value));
+        self.regions = regions;
+    }
+
+    /// A region can hold 2^36 addresses with values up to 2^36, so the
+    /// sum needs more than 64 bits.
+    fn sum(&self) -> u128 {
+        self.regions
+            .iter()
+            .map(|(pattern, value)| pattern.len() as u128 * *value as u128)
+            .sum()
+    }
+}
+
+#[test]
+fn test_apply_instructions_part2() {
+    let input = "mask = 000000000000000000000000000000X1001X
+mem[42] = 100
+mask = 00000000000000000000000000000000X0XX
+mem[26] = 1";
+    assert_eq!(apply_instructions_part2(&parse_input(input)).sum(), 208);
+
+    // Every floating bit set is fine without enumerating addresses
+    let input = "mask = XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX
+mem[0] = 1
+mask = 000000000000000000000000000000000000
+mem[7] = 5";
+    assert_eq!(apply_instructions_part2(&parse_input(input)).sum(), (1 << 36) - 1 + 5);
+
+    let input = "mask = XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX
+mem[0] = 68719476735";
+    assert_eq!(apply_instructions_part2(&parse_input(input)).sum(), (1 << 36) * ((1 << 36) - 1));
+}
+
 #[derive(Debug)]
 enum Instruction {
     Mask(Vec<Option<bool>>),
@@ -2,53 +2,132 @@ use std::collections::HashMap;
 
 fn main() {
     let input = parse_input(INPUT);
-    println!("part1: {}", simulate_game(2020, &input));
-    println!("part2: {}", simulate_game(30000000, &input));
+    println!("part1: {}", nth_spoken(&input, 2020));
+    println!("part2: {}", nth_spoken(&input, 30000000));
+
+    // Optionally find when a number first comes up
+    if let Some(arg) = std::env::args().nth(1) {
+        let number = arg.parse().expect("parse number to look for");
+        match first_turn_spoken(&input, number, 30000000) {
+            Some(turn) => println!("{} is first spoken on turn {}", number, turn),
+            None => println!("{} isn't spoken in the first 30000000 turns", number),
+        }
+    }
 }
 
-fn simulate_game(stop: usize, starting: &[usize]) -> usize {
-    let mut turn_prev_spoken: HashMap<usize, usize> = HashMap::new();
-    let mut turn_last_spoken: HashMap<usize, usize> = HashMap::new();
-    let mut last_spoken = 0;
-    for (i, start) in starting.iter().enumerate() {
-        turn_last_spoken.insert(*start, i + 1);
-        last_spoken = *start;
+/// Numbers below this are tracked in a flat array, and anything larger
+/// goes in a map. Spoken numbers are always ages, so they are smaller
+/// than the turn count, and only the starting numbers can be huge.
+const MAX_DENSE_NUMBERS: usize = 1 << 27;
+
+/// The sequence of numbers spoken in the elves' game.
+struct MemoryGame {
+    starting: Vec<u32>,
+    turn: u32,
+    last_spoken: Option<u32>,
+    // Turn each number was last spoken on, not counting the most recent
+    // turn. Zero means never, since turns start at 1.
+    last_seen: Vec<u32>,
+    last_seen_sparse: HashMap<u32, u32>,
+}
+
+impl MemoryGame {
+    /// Sets up a game that is expected to run for about `turns` turns.
+    /// Going past that still works, just slower.
+    fn new(starting: &[u32], turns: usize) -> Self {
+        MemoryGame {
+            starting: starting.to_vec(),
+            turn: 0,
+            last_spoken: None,
+            last_seen: vec![0; turns.min(MAX_DENSE_NUMBERS)],
+            last_seen_sparse: HashMap::new(),
+        }
+    }
+
+    /// Records that `number` was spoken on `turn` and returns the turn it
+    /// was spoken on before that.
+    fn record(&mut self, number: u32, turn: u32) -> Option<u32> {
+        let previous = match self.last_seen.get_mut(number as usize) {
+            Some(seen) => std::mem::replace(seen, turn),
+            None => self.last_seen_sparse.insert(number, turn).unwrap_or(0),
+        };
+        (previous != 0).then_some(previous)
     }
+}
 
-    for turn in (starting.len() + 1)..=stop {
-        let new_last_spoken = match (turn_prev_spoken.get(&last_spoken), turn_last_spoken.get(&last_spoken)) {
-            (None, None) => {
-                0
-            }
-            (None, Some(_)) => {
-                0
-            }
-            (Some(_), None) => panic!("internal error, found previous but not last for {}", last_spoken),
-            (Some(&prev_turn), Some(&last_turn)) => {
-                last_turn - prev_turn
+impl Iterator for MemoryGame {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        let turn = self.turn.checked_add(1)?;
+        let spoken = match self.last_spoken {
+            None => *self.starting.first()?,
+            Some(last) => {
+                let previous = self.record(last, self.turn);
+                match self.starting.get(self.turn as usize) {
+                    Some(&start) => start,
+                    None => previous.map_or(0, |previous| self.turn - previous),
+                }
             }
         };
-        if let Some(&last_turn) = turn_last_spoken.get(&new_last_spoken) {
-            turn_prev_spoken.insert(new_last_spoken, last_turn);
-        }
-        turn_last_spoken.insert(new_last_spoken, turn);
-        last_spoken = new_last_spoken;
+        self.turn = turn;
+        self.last_spoken = Some(spoken);
+        Some(spoken)
     }
-    last_spoken
 }
 
 #[test]
-fn test_simulate_game() {
-    assert_eq!(simulate_game(2020, &parse_input("1,3,2")), 1);
-    assert_eq!(simulate_game(2020, &parse_input("2,1,3")), 10);
-    assert_eq!(simulate_game(2020, &parse_input("1,2,3")), 27);
-    assert_eq!(simulate_game(2020, &parse_input("3,1,2")), 1836);
+fn test_memory_game() {
+    let example = parse_input(_EXAMPLE);
+    let expected = vec![0, 3, 6, 0, 3, 3, 1, 0, 4, 0];
+    assert_eq!(MemoryGame::new(&example, 10).take(10).collect::<Vec<_>>(), expected);
+
+    // Numbers that don't fit in the array use the map instead
+    let starting = vec![2000, 3, 2000];
+    let dense: Vec<u32> = MemoryGame::new(&starting, 3000).take(3000).collect();
+    let sparse: Vec<u32> = MemoryGame::new(&starting, 5).take(3000).collect();
+    assert_eq!(dense, sparse);
+    assert_eq!(&dense[..5], &[2000, 3, 2000, 2, 0]);
+
+    assert_eq!(MemoryGame::new(&[], 10).next(), None);
+}
+
+fn nth_spoken(starting: &[u32], turn: usize) -> u32 {
+    MemoryGame::new(starting, turn)
+        .nth(turn - 1)
+        .expect("game ended early")
+}
+
+#[test]
+fn test_nth_spoken() {
+    assert_eq!(nth_spoken(&parse_input(_EXAMPLE), 2020), 436);
+    assert_eq!(nth_spoken(&parse_input("1,3,2"), 2020), 1);
+    assert_eq!(nth_spoken(&parse_input("2,1,3"), 2020), 10);
+    assert_eq!(nth_spoken(&parse_input("1,2,3"), 2020), 27);
+    assert_eq!(nth_spoken(&parse_input("3,1,2"), 2020), 1836);
+}
+
+/// Returns the turn `number` is first spoken on, looking at up to
+/// `max_turns` turns.
+fn first_turn_spoken(starting: &[u32], number: u32, max_turns: usize) -> Option<usize> {
+    MemoryGame::new(starting, max_turns)
+        .take(max_turns)
+        .position(|spoken| spoken == number)
+        .map(|i| i + 1)
+}
+
+#[test]
+fn test_first_turn_spoken() {
+    let example = parse_input(_EXAMPLE);
+    assert_eq!(first_turn_spoken(&example, 6, 10), Some(3));
+    assert_eq!(first_turn_spoken(&example, 4, 10), Some(9));
+    assert_eq!(first_turn_spoken(&example, 5, 10), None);
 }
 
-fn parse_input(input: &str) -> Vec<usize> {
+fn parse_input(input: &str) -> Vec<u32> {
     input
         .split(',')
-        .map(|c| c.parse::<usize>().expect("parse input number"))
+        .map(|c| c.parse::<u32>().expect("parse input number"))
         .collect()
 }
 
@@ -1,10 +1,20 @@
-use std::collections::HashSet;
-
 fn main() {
-    let input = parse_input(_EXAMPLE2);
+    let input = parse_input(INPUT);
     println!("part1: {}", nearby_error_rate(&input));
+    match part2_departure_product(&input) {
+        Ok(product) => println!("part2: {}", product),
+        Err(err) => println!("part2: couldn't derive fields: {}", err),
+    }
+}
 
-    println!("{:?}", derive_fields(&input));
+fn part2_departure_product(input: &Input) -> Result<u64, FieldDerivationError> {
+    let fields = derive_fields(input)?;
+    Ok(fields
+        .iter()
+        .zip(input.your_ticket.iter())
+        .filter(|(def, _)| def.name.starts_with("departure"))
+        .map(|(_, &value)| value as u64)
+        .product())
 }
 
 fn nearby_error_rate(input: &Input) -> u32 {
@@ -33,43 +43,181 @@ fn field_def_valid(definition: &FieldDefinition, field: u32) -> bool {
     definition.ranges.iter().any(|(min, max)| field >= *min && field <= *max)
 }
 
-fn derive_fields<'a>(input: &'a Input) -> Vec<FieldDefinition<'a>> {
+/// Works out which field definition each ticket column holds, so the
+/// result is indexed by column.
+fn derive_fields<'a>(input: &'a Input) -> Result<Vec<&'a FieldDefinition<'a>>, FieldDerivationError> {
     // Filter out invalid tickets
     let valid_nearby = input
         .nearby_tickets
         .iter()
-        .filter(|ticket| invalid_fields(&input.field_definitions, ticket).len() == 0)
-        .cloned()
-        .collect::<Vec<Ticket>>();
+        .filter(|ticket| invalid_fields(&input.field_definitions, ticket).is_empty())
+        .collect::<Vec<&Ticket>>();
 
-    // Find possible field definitions for each position
-    let all_fields: HashSet<FieldDefinition> = input
+    // candidates[i][column] is true if every valid ticket's value in
+    // that column fits field definition i
+    let num_columns = input.your_ticket.len();
+    let candidates: Vec<Vec<bool>> = input
         .field_definitions
         .iter()
-        .cloned()
-        .collect();
-    let mut possible_fields: Vec<HashSet<FieldDefinition>> = valid_nearby[0]
-        .iter()
-        .map(|_| all_fields.clone())
+        .map(|def| {
+            (0..num_columns)
+                .map(|column| valid_nearby.iter().all(|ticket| field_def_valid(def, ticket[column])))
+                .collect()
+        })
         .collect();
-    for ticket in valid_nearby {
-        println!("before ticket {:?} {:#?}", ticket, possible_fields);
-        for (i, ticket_field) in ticket.iter().enumerate() {
-            possible_fields[i].retain(|def| field_def_valid(def, *ticket_field));
+
+    let field_columns = unique_perfect_matching(&candidates).map_err(|err| match err {
+        MatchingError::Infeasible { row } => FieldDerivationError::Infeasible {
+            field: input.field_definitions[row].name.to_string(),
+        },
+        MatchingError::Ambiguous { row, columns } => FieldDerivationError::Ambiguous {
+            field: input.field_definitions[row].name.to_string(),
+            columns,
+        },
+    })?;
+
+    let mut fields = vec![None; num_columns];
+    for (def, column) in input.field_definitions.iter().zip(field_columns) {
+        fields[column] = Some(def);
+    }
+    fields
+        .into_iter()
+        .enumerate()
+        .map(|(column, def)| def.ok_or(FieldDerivationError::UnusedColumn { column }))
+        .collect()
+}
+
+#[test]
+fn test_derive_fields() {
+    let input = parse_input(_EXAMPLE2);
+    let names: Vec<&str> = derive_fields(&input).unwrap().iter().map(|def| def.name).collect();
+    assert_eq!(names, vec!["row", "class", "seat"]);
+
+    // Swapping columns would satisfy both fields
+    let input = parse_input("a: 1-2 or 5-6\nb: 1-2 or 5-6\n\nyour ticket:\n1,2\n\nnearby tickets:\n2,1");
+    assert_eq!(
+        derive_fields(&input).unwrap_err(),
+        FieldDerivationError::Ambiguous { field: "a".to_string(), columns: (0, 1) },
+    );
+
+    // Fields a and b both only fit in the first column
+    let input = parse_input(
+        "a: 1-2 or 9-9\nb: 1-2 or 9-9\nc: 3-4 or 9-9\n\nyour ticket:\n1,3,3\n\nnearby tickets:\n1,3,3\n2,4,4",
+    );
+    assert!(matches!(derive_fields(&input), Err(FieldDerivationError::Infeasible { .. })));
+}
+
+#[derive(Debug, PartialEq)]
+enum FieldDerivationError {
+    /// There is no way to give this field a column that still leaves a
+    /// column for every other field
+    Infeasible { field: String },
+    /// This field could go in either column while still finding a
+    /// column for every other field
+    Ambiguous { field: String, columns: (usize, usize) },
+    /// There are more columns than fields
+    UnusedColumn { column: usize },
+}
+
+impl std::fmt::Display for FieldDerivationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FieldDerivationError::Infeasible { field } => write!(f, "no column left for field {:?}", field),
+            FieldDerivationError::Ambiguous { field, columns: (x, y) } => {
+                write!(f, "field {:?} could be in column {} or {}", field, x, y)
+            }
+            FieldDerivationError::UnusedColumn { column } => write!(f, "no field fits column {}", column),
         }
-        println!("after ticket {:?} {:#?}", ticket, possible_fields);
     }
+}
 
-    // Eliminate ambiguous fields
-    // TODO
+#[derive(Debug, PartialEq)]
+enum MatchingError {
+    Infeasible { row: usize },
+    Ambiguous { row: usize, columns: (usize, usize) },
+}
 
-    possible_fields
-        .iter()
-        .map(|possibs| {
-            assert!(possibs.len() == 1);
-            possibs.into_iter().nth(0).expect("couldn't get first def").clone()
-        })
-        .collect()
+/// Given a bipartite graph as a matrix where `candidates[row][column]`
+/// is true if there is an edge, finds the column for each row such that
+/// no two rows share a column. It is an error if there is no such
+/// matching or if there is more than one.
+fn unique_perfect_matching(candidates: &[Vec<bool>]) -> Result<Vec<usize>, MatchingError> {
+    let num_columns = candidates.first().map_or(0, |row| row.len());
+    let mut column_rows: Vec<Option<usize>> = vec![None; num_columns];
+
+    // Kuhn's algorithm: find an augmenting path for each row in turn
+    for row in 0..candidates.len() {
+        let mut visited = vec![false; num_columns];
+        if !augment(candidates, row, None, &mut column_rows, &mut visited) {
+            return Err(MatchingError::Infeasible { row });
+        }
+    }
+
+    let mut row_columns = vec![0; candidates.len()];
+    for (column, row) in column_rows.iter().enumerate() {
+        if let Some(row) = row {
+            row_columns[*row] = column;
+        }
+    }
+
+    // The matching is unique if no row can be rematched to a different
+    // column without its current edge, since any other matching would
+    // give us an alternating path like that.
+    for (row, &column) in row_columns.iter().enumerate() {
+        let mut alternative = column_rows.clone();
+        alternative[column] = None;
+        let mut visited = vec![false; num_columns];
+        if augment(candidates, row, Some(column), &mut alternative, &mut visited) {
+            let other = alternative
+                .iter()
+                .position(|&r| r == Some(row))
+                .expect("augmented row has a column");
+            return Err(MatchingError::Ambiguous { row, columns: (column.min(other), column.max(other)) });
+        }
+    }
+
+    Ok(row_columns)
+}
+
+fn augment(
+    candidates: &[Vec<bool>],
+    row: usize,
+    banned_column: Option<usize>,
+    column_rows: &mut Vec<Option<usize>>,
+    visited: &mut Vec<bool>,
+) -> bool {
+    for column in 0..column_rows.len() {
+        if !candidates[row][column] || visited[column] || banned_column == Some(column) {
+            continue;
+        }
+        visited[column] = true;
+
+        // Only the starting row is banned from its old column
+        let free = match column_rows[column] {
+            None => true,
+            Some(other_row) => augment(candidates, other_row, None, column_rows, visited),
+        };
+        if free {
+            column_rows[column] = Some(row);
+            return true;
+        }
+    }
+    false
+}
+
+#[test]
+fn test_unique_perfect_matching() {
+    let t = true;
+    let f = false;
+    assert_eq!(unique_perfect_matching(&[vec![t, t], vec![t, f]]), Ok(vec![1, 0]));
+    assert_eq!(
+        unique_perfect_matching(&[vec![t, t, f], vec![t, t, f], vec![f, f, t]]),
+        Err(MatchingError::Ambiguous { row: 0, columns: (0, 1) }),
+    );
+    assert_eq!(
+        unique_perfect_matching(&[vec![t, f], vec![t, f]]),
+        Err(MatchingError::Infeasible { row: 1 }),
+    );
 }
 
 #[derive(Debug)]
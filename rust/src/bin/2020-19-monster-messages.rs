@@ -1,99 +1,334 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
 fn main() {
-    let input = parse_input(INPUT);
-    println!("part1: {}", num_rules_matched(&input, false));
-    println!("part2: {}", num_rules_matched(&input, true));
+    let mut input = parse_input(INPUT);
+    println!("part1: {}", num_rules_matched(&input));
+
+    apply_rule_replacements(&mut input.rules, PART2_REPLACEMENTS);
+    println!("part2: {}", num_rules_matched(&input));
+
+    // Pass a message on the command line to see how it parses
+    if let Some(message) = std::env::args().nth(1) {
+        match parse_tree(&input.rules, &message) {
+            Some(tree) => print!("{}", tree),
+            None => println!("{} doesn't match", message),
+        }
+    }
 }
 
-fn num_rules_matched(input: &Input, part2_twist: bool) -> usize {
+// The part two twist is to replace these two rules, which makes the
+// grammar recursive. The Earley parser below handles arbitrary
+// recursion, so nothing else needs to change.
+const PART2_REPLACEMENTS: &str = "8: 42 | 42 8\n11: 42 31 | 42 11 31";
+
+fn num_rules_matched(input: &Input) -> usize {
     input
         .input_strings
         .iter()
-        .filter(|input_string| rules_match_input_string(&input.rules, input_string, part2_twist))
+        .filter(|input_string| rules_match_input_string(&input.rules, input_string))
         .count()
 }
 
-fn rules_match_input_string(rules: &HashMap<usize, Rule>, input: &str, part2_twist: bool) -> bool {
+fn rules_match_input_string(rules: &HashMap<usize, Rule>, input: &str) -> bool {
     let input_chars: Vec<char> = input.chars().collect();
-    let (matched, input_index) = rule_matches(0, rules, 0, &input_chars, part2_twist);
-    matched && input_index == input_chars.len()
+    EarleyChart::build(rules, 0, &input_chars).accepts()
+}
+
+/// Returns the parse tree for `input` starting from rule 0, or `None`
+/// if the input doesn't match the grammar. If the grammar is ambiguous
+/// then one of the possible trees is returned.
+fn parse_tree(rules: &HashMap<usize, Rule>, input: &str) -> Option<ParseTree> {
+    let input_chars: Vec<char> = input.chars().collect();
+    EarleyChart::build(rules, 0, &input_chars).parse_tree()
+}
+
+#[test]
+fn test_num_rules_matched() {
+    assert_eq!(num_rules_matched(&parse_input(_EXAMPLE1)), 2);
+
+    let mut input = parse_input(_EXAMPLE2);
+    assert_eq!(num_rules_matched(&input), 3);
+    apply_rule_replacements(&mut input.rules, PART2_REPLACEMENTS);
+    assert_eq!(num_rules_matched(&input), 12);
 }
 
-fn rule_matches(rule_index: usize, rules: &HashMap<usize, Rule>, input_index: usize, input: &[char], part2_twist: bool) -> (bool, usize) {
-    if input_index >= input.len() {
-        return (false, input_index);
+#[test]
+fn test_left_recursion() {
+    let input = parse_input("0: 0 1 | 2\n1: \"a\"\n2: \"b\"\n\nb\nbaaa\nab\n");
+    assert!(rules_match_input_string(&input.rules, "b"));
+    assert!(rules_match_input_string(&input.rules, "baaa"));
+    assert!(!rules_match_input_string(&input.rules, "ab"));
+    assert!(!rules_match_input_string(&input.rules, ""));
+}
+
+#[test]
+fn test_parse_tree() {
+    let input = parse_input(_EXAMPLE1);
+    assert_eq!(parse_tree(&input.rules, "aaaabbb"), None);
+
+    let tree = parse_tree(&input.rules, "ababbb").expect("should parse");
+    assert_eq!(
+        tree,
+        ParseTree::Node {
+            rule: 0,
+            children: vec![
+                ParseTree::Leaf('a'),
+                ParseTree::Node {
+                    rule: 1,
+                    children: vec![
+                        ParseTree::Node {
+                            rule: 3,
+                            children: vec![ParseTree::Leaf('b'), ParseTree::Leaf('a')],
+                        },
+                        ParseTree::Node {
+                            rule: 2,
+                            children: vec![ParseTree::Leaf('b'), ParseTree::Leaf('b')],
+                        },
+                    ],
+                },
+                ParseTree::Leaf('b'),
+            ],
+        },
+    );
+}
+
+/// An Earley item is a position within one alternative of a rule. The
+/// rule's symbols before `dot` have matched the input starting at
+/// `origin`. Literal rules have a single alternative with one symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct EarleyItem {
+    rule: usize,
+    alt: usize,
+    dot: usize,
+    origin: usize,
+}
+
+impl EarleyItem {
+    fn advance(self) -> Self {
+        EarleyItem { dot: self.dot + 1, ..self }
     }
+}
+
+#[derive(Debug, PartialEq)]
+enum Symbol {
+    Terminal(char),
+    NonTerminal(usize),
+}
+
+/// The result of running an Earley parser over some input. `sets[k]`
+/// holds every item that is still viable after consuming the first `k`
+/// characters.
+struct EarleyChart<'a> {
+    rules: &'a HashMap<usize, Rule>,
+    start_rule: usize,
+    sets: Vec<Vec<EarleyItem>>,
+}
+
+impl<'a> EarleyChart<'a> {
+    fn build(rules: &'a HashMap<usize, Rule>, start_rule: usize, input: &[char]) -> Self {
+        let mut sets: Vec<Vec<EarleyItem>> = vec![Vec::new(); input.len() + 1];
+        let mut seen: Vec<HashSet<EarleyItem>> = vec![HashSet::new(); input.len() + 1];
+
+        // Items in each set indexed by the rule they are waiting on, so
+        // completing a rule doesn't have to scan the whole origin set.
+        let mut waiting: Vec<HashMap<usize, Vec<EarleyItem>>> = vec![HashMap::new(); input.len() + 1];
 
-    //println!("rule_matches. rule_index: {}, rule: {:?}, input_index: {}", rule_index, rules[&rule_index], input_index);
-
-    // The part two twist is to change these two rules:
-    // 8: 42 | 42 8
-    // 11: 42 31 | 42 11 31
-    //
-    // In the part 2 example and my input, rule 0 is "0: 8 11". Both
-    // rules 8 and 11 match 42 in succession. Also, rules 8 and 11 are
-    // _only_ referenced in rule 0, so we should just handle rule 0
-    // special.
-    //
-    // Rule 0 expanded looks like "(42 | 42 8) (42 31 | 42 11 31)". We
-    // match 42 n times, and then we match 31 m times. We succeed when
-    // n >= m + 1 and m >= 1 (so n >= 2).
-    if part2_twist && rule_index == 0 {
-        let mut input_index = input_index;
-
-        let mut times_rule_42_matched = 0;
-        loop {
-            let (matched, new_input_index) = rule_matches(42, rules, input_index, input, part2_twist);
-            if !matched {
-                break;
+        let mut add_item = |sets: &mut Vec<Vec<EarleyItem>>,
+                            waiting: &mut Vec<HashMap<usize, Vec<EarleyItem>>>,
+                            k: usize,
+                            item: EarleyItem| {
+            if seen[k].insert(item) {
+                sets[k].push(item);
+                if let Some(Symbol::NonTerminal(rule)) = next_symbol(rules, &item) {
+                    waiting[k].entry(rule).or_default().push(item);
+                }
             }
-            input_index = new_input_index;
-            times_rule_42_matched += 1;
-        }
-        if times_rule_42_matched < 2 {
-            return (false, input_index);
+        };
+
+        for alt in 0..num_alternatives(&rules[&start_rule]) {
+            add_item(&mut sets, &mut waiting, 0, EarleyItem { rule: start_rule, alt, dot: 0, origin: 0 });
         }
 
-        let mut times_rule_31_matched = 0;
-        loop {
-            let (matched, new_input_index) = rule_matches(31, rules, input_index, input, part2_twist);
-            if !matched {
-                break;
+        for k in 0..sets.len() {
+            // Rules that completed without consuming any input at this
+            // position. Items that are predicted after the completion
+            // happened need to be advanced over these too.
+            let mut nullable = HashSet::new();
+
+            let mut i = 0;
+            while i < sets[k].len() {
+                let item = sets[k][i];
+                i += 1;
+
+                match next_symbol(rules, &item) {
+                    Some(Symbol::Terminal(c)) => {
+                        if input.get(k) == Some(&c) {
+                            add_item(&mut sets, &mut waiting, k + 1, item.advance());
+                        }
+                    }
+                    Some(Symbol::NonTerminal(rule)) => {
+                        for alt in 0..num_alternatives(&rules[&rule]) {
+                            add_item(&mut sets, &mut waiting, k, EarleyItem { rule, alt, dot: 0, origin: k });
+                        }
+                        if nullable.contains(&rule) {
+                            add_item(&mut sets, &mut waiting, k, item.advance());
+                        }
+                    }
+                    None => {
+                        if item.origin == k {
+                            nullable.insert(item.rule);
+                        }
+                        let parents = waiting[item.origin].get(&item.rule).cloned().unwrap_or_default();
+                        for parent in parents {
+                            add_item(&mut sets, &mut waiting, k, parent.advance());
+                        }
+                    }
+                }
             }
-            input_index = new_input_index;
-            times_rule_31_matched += 1;
         }
 
-        if times_rule_31_matched < 1 {
-            return (false, input_index);
+        EarleyChart { rules, start_rule, sets }
+    }
+
+    fn accepts(&self) -> bool {
+        let end = self.sets.len() - 1;
+        self.sets[end].iter().any(|item| {
+            item.rule == self.start_rule && item.origin == 0 && next_symbol(self.rules, item).is_none()
+        })
+    }
+
+    fn parse_tree(&self) -> Option<ParseTree> {
+        // Every completed item tells us that an alternative of a rule
+        // spans input[origin..end]. We use those spans to walk down
+        // from the start rule and pick split points for each sequence.
+        let mut completed = HashSet::new();
+        for (end, set) in self.sets.iter().enumerate() {
+            for item in set {
+                if next_symbol(self.rules, item).is_none() {
+                    completed.insert(Span { rule: item.rule, alt: item.alt, start: item.origin, end });
+                }
+            }
         }
 
-        return (times_rule_42_matched >= times_rule_31_matched + 1, input_index);
+        let builder = TreeBuilder { rules: self.rules, completed };
+        builder.build_rule(self.start_rule, 0, self.sets.len() - 1, &mut HashSet::new())
+    }
+}
+
+fn num_alternatives(rule: &Rule) -> usize {
+    match rule {
+        Rule::Literal(_) => 1,
+        Rule::Alternative(alts) => alts.len(),
+    }
+}
+
+fn next_symbol(rules: &HashMap<usize, Rule>, item: &EarleyItem) -> Option<Symbol> {
+    match &rules[&item.rule] {
+        Rule::Literal(c) => (item.dot == 0).then_some(Symbol::Terminal(*c)),
+        Rule::Alternative(alts) => alts[item.alt].get(item.dot).map(|rule| Symbol::NonTerminal(*rule)),
     }
+}
 
-    match &rules[&rule_index] {
-        Rule::Literal(c) => {
-            (input[input_index] == *c, input_index + 1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Span {
+    rule: usize,
+    alt: usize,
+    start: usize,
+    end: usize,
+}
+
+struct TreeBuilder<'a> {
+    rules: &'a HashMap<usize, Rule>,
+    completed: HashSet<Span>,
+}
+
+impl<'a> TreeBuilder<'a> {
+    fn spans(&self, rule: usize, start: usize, end: usize) -> bool {
+        (0..num_alternatives(&self.rules[&rule]))
+            .any(|alt| self.completed.contains(&Span { rule, alt, start, end }))
+    }
+
+    // `path` holds the (rule, start, end) triples we are currently
+    // inside of. Cyclic grammars like "1: 2 | a" and "2: 1" can derive
+    // the same span forever, so we refuse to revisit one.
+    fn build_rule(
+        &self,
+        rule: usize,
+        start: usize,
+        end: usize,
+        path: &mut HashSet<(usize, usize, usize)>,
+    ) -> Option<ParseTree> {
+        if !path.insert((rule, start, end)) {
+            return None;
         }
-        Rule::Alternative(alts) => {
-            'alt_outer: for alt in alts {
-                //println!("testing alt: {:?}", alt);
-                let mut alt_input_index = input_index;
-                for sub_rule in alt {
-                    let (matched, new_input_index) = rule_matches(*sub_rule, rules, alt_input_index, input, part2_twist);
-                    if !matched {
-                        continue 'alt_outer;
-                    }
-                    alt_input_index = new_input_index;
+
+        let tree = match &self.rules[&rule] {
+            Rule::Literal(c) => self.spans(rule, start, end).then_some(ParseTree::Leaf(*c)),
+            Rule::Alternative(alts) => alts
+                .iter()
+                .enumerate()
+                .filter(|(alt, _)| self.completed.contains(&Span { rule, alt: *alt, start, end }))
+                .find_map(|(_, seq)| self.build_sequence(seq, start, end, path))
+                .map(|children| ParseTree::Node { rule, children }),
+        };
+
+        path.remove(&(rule, start, end));
+        tree
+    }
+
+    fn build_sequence(
+        &self,
+        seq: &[usize],
+        start: usize,
+        end: usize,
+        path: &mut HashSet<(usize, usize, usize)>,
+    ) -> Option<Vec<ParseTree>> {
+        let (first, rest) = match seq.split_first() {
+            None => return (start == end).then(Vec::new),
+            Some(split) => split,
+        };
+
+        (start..=end).find_map(|mid| {
+            if !self.spans(*first, start, mid) {
+                return None;
+            }
+            let head = self.build_rule(*first, start, mid, path)?;
+            let mut children = self.build_sequence(rest, mid, end, path)?;
+            children.insert(0, head);
+            Some(children)
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ParseTree {
+    Leaf(char),
+    Node { rule: usize, children: Vec<ParseTree> },
+}
+
+impl ParseTree {
+    fn fmt_indented(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        match self {
+            ParseTree::Leaf(c) => writeln!(f, "{}{:?}", indent, c),
+            ParseTree::Node { rule, children } => {
+                writeln!(f, "{}{}", indent, rule)?;
+                for child in children {
+                    child.fmt_indented(f, depth + 1)?;
                 }
-                return (true, alt_input_index);
+                Ok(())
             }
-            (false, input_index)
         }
     }
 }
 
+impl fmt::Display for ParseTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
 #[derive(Debug)]
 struct Input<'a> {
     rules: HashMap<usize, Rule>,
@@ -108,44 +343,55 @@ enum Rule {
 
 type RuleSequence = Vec<usize>;
 
-
-fn parse_input(input: &str) -> Input {
+fn parse_input(input: &str) -> Input<'_> {
     let mut input_lines = input.lines();
 
     // Rules
     let mut rules = HashMap::new();
-    while let Some(rule_line) = input_lines.next() {
-        if rule_line == "" {
+    for rule_line in input_lines.by_ref() {
+        if rule_line.is_empty() {
             break;
         }
+        let (rule_index, rule) = parse_rule(rule_line);
+        rules.insert(rule_index, rule);
+    }
 
-        let (rule_index_str, rule_str) = rule_line.split_once(": ").expect("no colon in rule");
-        let rule_index = rule_index_str.parse().expect("parse rule index");
-        if rule_str.chars().nth(0).expect("no first char") == '"' {
-            let literal_char = rule_str.chars().skip(1).nth(0).expect("no char literal");
-            rules.insert(rule_index, Rule::Literal(literal_char));
-        } else {
-            let mut alternatives = Vec::new();
-            let mut this_alt = Vec::new();
-
-            for word in rule_str.split_whitespace() {
-                if word == "|" {
-                    alternatives.push(this_alt);
-                    this_alt = Vec::new();
-                } else {
-                    this_alt.push(word.parse().expect("parsing usize literal in rule"));
-                }
-            }
+    // Input strings
+    let input_strings = input_lines.collect();
+
+    Input { rules, input_strings }
+}
+
+fn parse_rule(rule_line: &str) -> (usize, Rule) {
+    let (rule_index_str, rule_str) = rule_line.split_once(": ").expect("no colon in rule");
+    let rule_index = rule_index_str.parse().expect("parse rule index");
+
+    if rule_str.starts_with('"') {
+        let literal_char = rule_str.chars().nth(1).expect("no char literal");
+        return (rule_index, Rule::Literal(literal_char));
+    }
+
+    let mut alternatives = Vec::new();
+    let mut this_alt = Vec::new();
 
+    for word in rule_str.split_whitespace() {
+        if word == "|" {
             alternatives.push(this_alt);
-            rules.insert(rule_index, Rule::Alternative(alternatives));
+            this_alt = Vec::new();
+        } else {
+            this_alt.push(word.parse().expect("parsing usize literal in rule"));
         }
     }
 
-    // Input strings
-    let input_strings = input_lines.collect();
+    alternatives.push(this_alt);
+    (rule_index, Rule::Alternative(alternatives))
+}
 
-    Input { rules, input_strings }
+fn apply_rule_replacements(rules: &mut HashMap<usize, Rule>, replacements: &str) {
+    for rule_line in replacements.lines() {
+        let (rule_index, rule) = parse_rule(rule_line);
+        rules.insert(rule_index, rule);
+    }
 }
 
 const _EXAMPLE1: &str = r#"0: 4 1 5
@@ -1,8 +1,17 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 fn main() {
     let (player1, player2) = parse_input(INPUT);
     println!("part1: {}", simulate_until_win(&player1, &player2));
+
+    // Pass --log to see every round of every game
+    let log = std::env::args().any(|arg| arg == "--log");
+    let mut game = RecursiveCombat::new(log);
+    let (_, winning_deck) = game.play(&player1, &player2);
+    println!("part2: {}", deck_score(&winning_deck));
+    for line in game.log.iter().flatten() {
+        println!("{}", line);
+    }
 }
 
 type Deck = Vec<u64>;
@@ -23,15 +32,165 @@ fn simulate_until_win(player1: &[u64], player2: &[u64]) -> u64 {
             player2_deck.push_back(player1_card);
         }
 
-        if player1_deck.len() == 0 {
+        if player1_deck.is_empty() {
             return deck_score(player2_deck.make_contiguous());
         }
-        if player2_deck.len() == 0 {
+        if player2_deck.is_empty() {
             return deck_score(player1_deck.make_contiguous());
         }
     }
 }
 
+#[test]
+fn test_simulate_until_win() {
+    let (player1, player2) = parse_input(_EXAMPLE);
+    assert_eq!(simulate_until_win(&player1, &player2), 306);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Player {
+    One,
+    Two,
+}
+
+struct RecursiveCombat {
+    /// Winners of sub-games we've already played, keyed by the decks
+    /// the sub-game started with
+    winners: HashMap<(Deck, Deck), Player>,
+    /// Round by round history, if we are keeping one
+    log: Option<Vec<String>>,
+    games_started: usize,
+}
+
+impl RecursiveCombat {
+    fn new(log: bool) -> Self {
+        RecursiveCombat {
+            winners: HashMap::new(),
+            log: if log { Some(Vec::new()) } else { None },
+            games_started: 0,
+        }
+    }
+
+    fn log_line(&mut self, line: impl FnOnce() -> String) {
+        if let Some(log) = &mut self.log {
+            log.push(line());
+        }
+    }
+
+    /// Plays a game of Recursive Combat, returning the winner and
+    /// their deck at the end of the game.
+    fn play(&mut self, player1: &[u64], player2: &[u64]) -> (Player, Deck) {
+        self.games_started += 1;
+        let game = self.games_started;
+        self.log_line(|| format!("=== Game {} ===", game));
+
+        let mut player1_deck = VecDeque::from(player1.to_vec());
+        let mut player2_deck = VecDeque::from(player2.to_vec());
+        let mut seen_decks: HashSet<(Deck, Deck)> = HashSet::new();
+
+        for round in 1.. {
+            // If there was a previous round in this game that had
+            // exactly the same cards in the same order in the same
+            // players' decks, the game instantly ends in a win for
+            // player 1.
+            let decks = (Vec::from(player1_deck.clone()), Vec::from(player2_deck.clone()));
+            if !seen_decks.insert(decks) {
+                self.log_line(|| format!("Repeated decks, player 1 wins game {}!", game));
+                return (Player::One, player1_deck.into());
+            }
+
+            self.log_line(|| {
+                format!(
+                    "-- Round {} (Game {}) --\nPlayer 1's deck: {}\nPlayer 2's deck: {}",
+                    round,
+                    game,
+                    player1_deck.iter().map(u64::to_string).collect::<Vec<_>>().join(", "),
+                    player2_deck.iter().map(u64::to_string).collect::<Vec<_>>().join(", "),
+                )
+            });
+
+            let player1_card = player1_deck.pop_front().expect("player1 pop");
+            let player2_card = player2_deck.pop_front().expect("player2 pop");
+            self.log_line(|| format!("Player 1 plays: {}\nPlayer 2 plays: {}", player1_card, player2_card));
+
+            // If both players have at least as many cards remaining in
+            // their deck as the value of the card they just drew, the
+            // winner of the round is determined by playing a new game
+            // of Recursive Combat.
+            let round_winner = if player1_deck.len() as u64 >= player1_card
+                && player2_deck.len() as u64 >= player2_card
+            {
+                let sub_player1: Deck = player1_deck.iter().take(player1_card as usize).copied().collect();
+                let sub_player2: Deck = player2_deck.iter().take(player2_card as usize).copied().collect();
+                self.log_line(|| "Playing a sub-game to determine the winner...".to_string());
+                self.play_sub_game(sub_player1, sub_player2)
+            } else if player1_card > player2_card {
+                Player::One
+            } else {
+                Player::Two
+            };
+
+            self.log_line(|| format!("Player {:?} wins round {} of game {}!\n", round_winner, round, game));
+            match round_winner {
+                Player::One => {
+                    player1_deck.push_back(player1_card);
+                    player1_deck.push_back(player2_card);
+                }
+                Player::Two => {
+                    player2_deck.push_back(player2_card);
+                    player2_deck.push_back(player1_card);
+                }
+            }
+
+            if player1_deck.is_empty() {
+                self.log_line(|| format!("The winner of game {} is player 2!\n", game));
+                return (Player::Two, player2_deck.into());
+            }
+            if player2_deck.is_empty() {
+                self.log_line(|| format!("The winner of game {} is player 1!\n", game));
+                return (Player::One, player1_deck.into());
+            }
+        }
+
+        unreachable!()
+    }
+
+    fn play_sub_game(&mut self, player1: Deck, player2: Deck) -> Player {
+        let key = (player1, player2);
+        if let Some(&winner) = self.winners.get(&key) {
+            self.log_line(|| format!("Already played this sub-game, player {:?} wins", winner));
+            return winner;
+        }
+
+        let (winner, _) = self.play(&key.0, &key.1);
+        self.log_line(|| "...anyway, back to the previous game.".to_string());
+        self.winners.insert(key, winner);
+        winner
+    }
+}
+
+#[test]
+fn test_recursive_combat() {
+    let (player1, player2) = parse_input(_EXAMPLE);
+    let mut game = RecursiveCombat::new(true);
+    let (winner, deck) = game.play(&player1, &player2);
+    assert_eq!(winner, Player::Two);
+    assert_eq!(deck, vec![7, 5, 6, 2, 4, 1, 10, 8, 9, 3]);
+    assert_eq!(deck_score(&deck), 291);
+
+    // The puzzle text plays 5 games, but one of the sub-games repeats
+    // and its winner comes from the cache
+    assert_eq!(game.games_started, 4);
+
+    let log = game.log.expect("no log");
+    assert_eq!(log[0], "=== Game 1 ===");
+    assert!(log.iter().any(|line| line.starts_with("Already played this sub-game")));
+
+    // This game would loop forever without the repeated decks rule
+    let (winner, _) = RecursiveCombat::new(false).play(&[43, 19], &[2, 29, 14]);
+    assert_eq!(winner, Player::One);
+}
+
 fn deck_score(deck: &[u64]) -> u64 {
     deck
         .iter()
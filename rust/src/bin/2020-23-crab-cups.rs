@@ -1,60 +1,80 @@
-use itertools::Itertools;
+use aoc::circular_list::CircularList;
 
 fn main() {
     let input = parse_input(INPUT);
 
-    let part1_next_values = simulate_turns(&input, 100);
-    let mut clockwise_from_one: Vec<usize> = vec![part1_next_values[1]];
-    for _ in 0..(input.len() - 2) {
-        clockwise_from_one.push(part1_next_values[*clockwise_from_one.last().unwrap()]);
-    }
-    let part1: String = clockwise_from_one.iter().map(|x| x.to_string()).collect();
-    println!("part1: {}", part1);
+    let mut game = CrabCups::new(&input, input.len());
+    game.play(100);
+    println!("part1: {}", game.labels_after_one());
 
-    let mut part2_input = input.clone();
-    for i in (input.len() + 1)..=1000000 {
-        part2_input.push(i as u32);
-    }
-    let part2_next_values = simulate_turns(&part2_input, 10000000);
-    let after_1 = part2_next_values[1];
-    let after_after_1 = part2_next_values[after_1];
+    let mut game = CrabCups::new(&input, 1000000);
+    game.play(10000000);
+    let after_1 = game.cups.next(1) as u64;
+    let after_after_1 = game.cups.next(after_1 as u32) as u64;
     let part2 = after_1 * after_after_1;
     println!("part2: {}", part2);
 }
 
-fn simulate_turns(input: &[u32], turns: usize) -> Vec<usize> {
-    // next_value[i] is the value that comes after i
-    let mut next_value: Vec<usize> = vec![0; input.len() + 1];
-    for i in 0..(input.len() - 1) {
-        next_value[input[i] as usize] = input[i + 1] as usize;
+struct CrabCups {
+    cups: CircularList,
+    current: u32,
+    max_label: u32,
+}
+
+impl CrabCups {
+    /// Starts with the cups from the input, followed by increasing labels
+    /// until there are `total_cups` cups.
+    fn new(input: &[u32], total_cups: usize) -> Self {
+        let max_label = total_cups as u32;
+        let labels = input.iter().copied().chain(input.len() as u32 + 1..=max_label);
+        CrabCups {
+            cups: CircularList::from_labels(labels),
+            current: input[0],
+            max_label,
+        }
     }
-    next_value[input[input.len() - 1] as usize] = input[0] as usize; // Wrap around
-    let mut current: usize = input[0] as usize;
 
-    for _ in 0..turns {
+    fn play(&mut self, moves: usize) {
+        for _ in 0..moves {
+            self.play_move();
+        }
+    }
+
+    fn play_move(&mut self) {
         // Pick up the next 3 cups
-        let mut picked_up = vec![next_value[current]];
-        picked_up.push(next_value[picked_up[0]]);
-        picked_up.push(next_value[picked_up[1]]);
-
-        // Find the destination cup
-        let mut dest = current - 1;
-        while dest == 0 || picked_up.iter().any(|x| *x == dest) {
-            if dest == 0 {
-                dest = input.len();
-            } else {
-                dest -= 1;
+        let picked_up = self.cups.splice_out(self.current, 3);
+
+        // The destination is the next lowest label that wasn't picked up,
+        // wrapping around to the highest label
+        let mut dest = self.current;
+        loop {
+            dest = if dest == 1 { self.max_label } else { dest - 1 };
+            if !self.cups.run_labels(picked_up).any(|label| label == dest) {
+                break;
             }
         }
 
         // Put cups back
-        next_value[current] = next_value[picked_up[2]];
-        next_value[picked_up[2]] = next_value[dest];
-        next_value[dest] = picked_up[0];
-        current = next_value[current];
+        self.cups.splice_in(dest, picked_up);
+        self.current = self.cups.next(self.current);
+    }
+
+    fn labels_after_one(&self) -> String {
+        self.cups.iter_from(1).skip(1).map(|x| x.to_string()).collect()
     }
+}
+
+#[test]
+fn test_crab_cups() {
+    let example = parse_input(_EXAMPLE);
+    let mut game = CrabCups::new(&example, example.len());
+    game.play(10);
+    assert_eq!(game.labels_after_one(), "92658374");
+    game.play(90);
+    assert_eq!(game.labels_after_one(), "67384529");
 
-    next_value
+    let game = CrabCups::new(&example, 12);
+    assert_eq!(game.cups.iter_from(7).collect::<Vec<_>>(), vec![7, 10, 11, 12, 3, 8, 9, 1, 2, 5, 4, 6]);
 }
 
 // Old, slow solution
@@ -1,34 +1,142 @@
-use std::collections::HashMap;
+use aoc::matrix::Matrix;
 
 fn main() {
     let input = parse_input(INPUT);
-    println!("part1: {}", count_fish_after_days(&input, 80));
-    println!("part1: {}", count_fish_after_days(&input, 256));
+    println!("part1: {}", count_fish(&input, &PUZZLE_TIMERS, 80, None).expect("overflow"));
+    println!("part2: {}", count_fish(&input, &PUZZLE_TIMERS, 256, None).expect("overflow"));
+
+    // The exact count overflows after a few hundred more days, but it can
+    // still be found modulo something.
+    let days = 1_000_000_000;
+    let modulus = 1_000_000_007;
+    println!(
+        "after {} days (mod {}): {}",
+        days,
+        modulus,
+        count_fish(&input, &PUZZLE_TIMERS, days, Some(modulus)).expect("overflow")
+    );
+}
+
+/// How long fish wait between spawning. A fish that just spawned is reset
+/// to `reset`, and a new fish starts at `newborn`.
+struct FishTimers {
+    reset: usize,
+    newborn: usize,
+}
+
+const PUZZLE_TIMERS: FishTimers = FishTimers { reset: 6, newborn: 8 };
+
+/// Counts fish after some days by raising the day's transition matrix to
+/// the number of days. Returns `None` if the count overflows, which can
+/// be avoided by giving a modulus.
+fn count_fish(input: &[usize], timers: &FishTimers, num_days: u64, modulus: Option<u64>) -> Option<u128> {
+    assert!(
+        timers.reset <= timers.newborn,
+        "reset timer {} can't be longer than newborn timer {}",
+        timers.reset,
+        timers.newborn
+    );
+    let counts = transition_matrix(timers)
+        .pow(num_days, modulus)?
+        .mul_vector(&timer_counts(input, timers), modulus)?;
+
+    let mut total: u128 = 0;
+    for count in counts {
+        total = total.checked_add(count)?;
+    }
+    Some(modulus.map_or(total, |m| total % m as u128))
 }
 
-fn count_fish_after_days(input: &[usize], num_days: usize) -> u64 {
-    // Populate counts
-    let mut days_left_counts = vec![0; 9];
+#[test]
+fn test_count_fish() {
+    let example = parse_input(_EXAMPLE);
+    assert_eq!(count_fish(&example, &PUZZLE_TIMERS, 18, None), Some(26));
+    assert_eq!(count_fish(&example, &PUZZLE_TIMERS, 80, None), Some(5934));
+    assert_eq!(count_fish(&example, &PUZZLE_TIMERS, 256, None), Some(26984457539));
+    assert_eq!(count_fish(&example, &PUZZLE_TIMERS, 256, Some(1000)), Some(539));
+    assert_eq!(count_fish(&example, &PUZZLE_TIMERS, 10_000, None), None);
+
+    // Check against the day by day simulation, with other timers too
+    let timers = [
+        PUZZLE_TIMERS,
+        FishTimers { reset: 2, newborn: 4 },
+        FishTimers { reset: 0, newborn: 5 },
+        FishTimers { reset: 4, newborn: 4 },
+    ];
+    for timers in &timers {
+        for days in 0..100 {
+            assert_eq!(
+                count_fish(&example, timers, days, None),
+                Some(simulate_fish(&example, timers, days)),
+                "reset {} newborn {} days {}",
+                timers.reset,
+                timers.newborn,
+                days
+            );
+        }
+    }
+}
+
+#[test]
+fn test_count_fish_same_timers() {
+    // Every fish at 0 turns into two fish at 4
+    let timers = FishTimers { reset: 4, newborn: 4 };
+    assert_eq!(count_fish(&[0, 0], &timers, 1, None), Some(4));
+    assert_eq!(count_fish(&[0], &timers, 5, None), Some(2));
+    assert_eq!(count_fish(&[0], &timers, 6, None), Some(4));
+}
+
+#[test]
+#[should_panic(expected = "reset timer 9 can't be longer than newborn timer 8")]
+fn test_count_fish_invalid_timers() {
+    count_fish(&[1], &FishTimers { reset: 9, newborn: 8 }, 10, None);
+}
+
+fn timer_counts(input: &[usize], timers: &FishTimers) -> Vec<u128> {
+    let mut counts = vec![0; timers.newborn + 1];
     for days_left in input {
-        days_left_counts[*days_left] += 1;
+        assert!(*days_left <= timers.newborn, "fish timer {} is past the newborn timer", days_left);
+        counts[*days_left] += 1;
     }
+    counts
+}
+
+/// Counts fish one day at a time, to check `count_fish` against.
+#[cfg(test)]
+fn simulate_fish(input: &[usize], timers: &FishTimers, num_days: u64) -> u128 {
+    let mut days_left_counts = timer_counts(input, timers);
 
-    // Simulate days
     for _ in 0..num_days {
         // Store new and reset fish in temp values
         let zero_fish = days_left_counts[0];
-        for days_left in 1..=8 {
+        for days_left in 1..=timers.newborn {
             days_left_counts[days_left - 1] = days_left_counts[days_left];
         }
 
         // Put new and reset fish back in
-        days_left_counts[6] += zero_fish;
-        days_left_counts[8] = zero_fish;
+        days_left_counts[timers.newborn] = zero_fish;
+        days_left_counts[timers.reset] += zero_fish;
     }
 
     days_left_counts.iter().sum()
 }
 
+/// Maps timer counts on one day to the counts on the next day. Entry
+/// (row, col) is how many fish with timer `row` a fish with timer `col`
+/// turns into.
+fn transition_matrix(timers: &FishTimers) -> Matrix {
+    let mut matrix = Matrix::zero(timers.newborn + 1);
+    for days_left in 1..=timers.newborn {
+        matrix.set(days_left - 1, days_left, 1);
+    }
+    // Added rather than set, since the two timers could be the same
+    for timer in [timers.reset, timers.newborn] {
+        let fish = matrix.get(timer, 0);
+        matrix.set(timer, 0, fish + 1);
+    }
+    matrix
+}
+
 fn parse_input(input: &str) -> Vec<usize> {
     input
         .split(",")
@@ -1,118 +1,224 @@
 fn main() {
-    let input = parse_input(INPUT);
+    let checker = DelimiterChecker::new(&CHUNK_DELIMITERS);
+    println!("part1: {}", part1_corrupted_score(&checker, INPUT));
+    println!("part2: {}", part2_middle_completion_score(&checker, INPUT));
 
-    let part1 = input
-        .iter()
-        .map(|line| corrupted_line_score(&line))
-        .sum::<u64>();
-    println!("part1: {}", part1);
+    // Pass file paths on the command line to lint them for mismatched
+    // delimiters. Angle brackets show up in too many other places in
+    // source code to check them.
+    let source_checker = DelimiterChecker::new(&SOURCE_DELIMITERS);
+    for path in std::env::args().skip(1) {
+        let contents = std::fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("couldn't read {}: {}", path, err));
+        match source_checker.check(&contents) {
+            CheckResult::Valid => println!("{}: ok", path),
+            CheckResult::Corrupted { position, expected: Some(expected), found } => println!(
+                "{}:{}:{}: expected {:?}, found {:?}",
+                path, position.line, position.column, expected, found
+            ),
+            CheckResult::Corrupted { position, expected: None, found } => println!(
+                "{}:{}:{}: unexpected {:?}",
+                path, position.line, position.column, found
+            ),
+            CheckResult::Incomplete { completion } => {
+                println!("{}: unclosed delimiters, needs {:?}", path, completion)
+            }
+        }
+    }
+}
+
+const CHUNK_DELIMITERS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')];
+
+const SOURCE_DELIMITERS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+const CORRUPTED_SCORES: [(char, u64); 4] = [(')', 3), (']', 57), ('}', 1197), ('>', 25137)];
+
+const COMPLETION_SCORES: [(char, u64); 4] = [(')', 1), (']', 2), ('}', 3), ('>', 4)];
+
+fn part1_corrupted_score(checker: &DelimiterChecker, input: &str) -> u64 {
+    input
+        .lines()
+        .map(|line| corrupted_score(&checker.check(line), &CORRUPTED_SCORES))
+        .sum()
+}
 
+fn part2_middle_completion_score(checker: &DelimiterChecker, input: &str) -> u64 {
     let mut completion_scores: Vec<u64> = input
-        .iter()
-        .map(|line| incomplete_line_score(&line))
-        .filter(|&score| score > 0)
+        .lines()
+        .filter_map(|line| match checker.check(line) {
+            CheckResult::Incomplete { completion } => Some(completion_score(&completion, &COMPLETION_SCORES)),
+            _ => None,
+        })
         .collect();
-    completion_scores.sort();
-    let part2 = completion_scores[completion_scores.len() / 2];
-    println!("part2: {}", part2);
+    completion_scores.sort_unstable();
+    completion_scores[completion_scores.len() / 2]
 }
 
-fn corrupted_line_score(line: &[Delim]) -> u64 {
-    let mut open_delim_stack: Vec<&DelimType> = Vec::new();
-    for delim in line {
-        match delim {
-            Delim::Open(delim_type) => {
-                open_delim_stack.push(delim_type);
-            },
-            Delim::Close(delim_type) => {
-                let last_open = open_delim_stack.pop().expect("no open delims left!");
-                if last_open != delim_type {
-                    return corrupt_char_score(delim_type);
-                }
-            },
-        }
-    }
-    0
+#[test]
+fn test_scores() {
+    let checker = DelimiterChecker::new(&CHUNK_DELIMITERS);
+    assert_eq!(part1_corrupted_score(&checker, _EXAMPLE), 26397);
+    assert_eq!(part2_middle_completion_score(&checker, _EXAMPLE), 288957);
 }
 
-fn corrupt_char_score(delim_type: &DelimType) -> u64 {
-    match delim_type {
-        DelimType::Paren => 3,
-        DelimType::SquareBracket => 57,
-        DelimType::CurlyBracket => 1197,
-        DelimType::AngleBracket => 25137,
+fn score_lookup(table: &[(char, u64)], c: char) -> u64 {
+    table
+        .iter()
+        .find(|(table_char, _)| *table_char == c)
+        .map(|(_, score)| *score)
+        .unwrap_or_else(|| panic!("no score for {:?}", c))
+}
+
+/// Score of the first illegal character, or 0 if the line isn't
+/// corrupted.
+fn corrupted_score(result: &CheckResult, table: &[(char, u64)]) -> u64 {
+    match result {
+        CheckResult::Corrupted { found, .. } => score_lookup(table, *found),
+        _ => 0,
     }
 }
 
-fn incomplete_line_score(line: &[Delim]) -> u64 {
-    let mut open_delim_stack: Vec<&DelimType> = Vec::new();
-    for delim in line {
-        match delim {
-            Delim::Open(delim_type) => {
-                open_delim_stack.push(delim_type);
-            },
-            Delim::Close(delim_type) => {
-                let last_open = open_delim_stack.pop().expect("no open delims left!");
-                if last_open != delim_type {
-                    return 0;
+fn completion_score(completion: &str, table: &[(char, u64)]) -> u64 {
+    completion
+        .chars()
+        .fold(0, |score, c| score * 5 + score_lookup(table, c))
+}
+
+/// Checks that delimiters in some text are balanced. Characters that
+/// aren't one of the configured delimiters are ignored. A pair can use
+/// the same character to open and close, like quotes, in which case it
+/// closes if it is the innermost open pair and opens otherwise.
+struct DelimiterChecker {
+    pairs: Vec<(char, char)>,
+}
+
+#[derive(Debug, PartialEq)]
+enum CheckResult {
+    Valid,
+    /// A closing delimiter didn't match the most recent open one.
+    /// `expected` is `None` when nothing was open at all.
+    Corrupted {
+        position: Position,
+        expected: Option<char>,
+        found: char,
+    },
+    /// The input ended with open delimiters. `completion` is the
+    /// shortest string that closes all of them.
+    Incomplete { completion: String },
+}
+
+/// 1-based line and column, counted in chars.
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Position {
+    line: usize,
+    column: usize,
+}
+
+impl DelimiterChecker {
+    fn new(pairs: &[(char, char)]) -> Self {
+        // Each character can only belong to one pair, or there'd be no way
+        // to tell which pair it is for
+        for (i, &(open, close)) in pairs.iter().enumerate() {
+            for &(other_open, other_close) in &pairs[i + 1..] {
+                for c in [open, close] {
+                    assert!(
+                        c != other_open && c != other_close,
+                        "{:?} is in both {:?} and {:?}",
+                        c,
+                        (open, close),
+                        (other_open, other_close)
+                    );
                 }
-            },
+            }
         }
+        DelimiterChecker { pairs: pairs.to_vec() }
     }
 
-    let mut score = 0;
-    for delim in open_delim_stack.iter().rev() {
-        score = score * 5 + completion_char_score(delim);
+    fn closer_for(&self, c: char) -> Option<char> {
+        self.pairs.iter().find(|(open, _)| *open == c).map(|(_, close)| *close)
     }
-    score
-}
 
-fn completion_char_score(delim_type: &DelimType) -> u64 {
-    match delim_type {
-        DelimType::Paren => 1,
-        DelimType::SquareBracket => 2,
-        DelimType::CurlyBracket => 3,
-        DelimType::AngleBracket => 4,
+    fn is_closer(&self, c: char) -> bool {
+        self.pairs.iter().any(|(_, close)| *close == c)
     }
-}
 
-#[derive(Debug, PartialEq)]
-enum Delim {
-    Open(DelimType),
-    Close(DelimType),
+    fn check(&self, input: &str) -> CheckResult {
+        // Stack of the closers we expect to see, innermost last
+        let mut expected_closers: Vec<char> = Vec::new();
+        let mut position = Position { line: 1, column: 0 };
+
+        for c in input.chars() {
+            if c == '\n' {
+                position.line += 1;
+                position.column = 0;
+                continue;
+            }
+            position.column += 1;
+
+            if expected_closers.last() == Some(&c) {
+                expected_closers.pop();
+            } else if let Some(close) = self.closer_for(c) {
+                expected_closers.push(close);
+            } else if self.is_closer(c) {
+                let expected = expected_closers.pop();
+                if expected != Some(c) {
+                    return CheckResult::Corrupted { position, expected, found: c };
+                }
+            }
+        }
+
+        if expected_closers.is_empty() {
+            CheckResult::Valid
+        } else {
+            CheckResult::Incomplete { completion: expected_closers.iter().rev().collect() }
+        }
+    }
 }
 
-#[derive(Debug, PartialEq)]
-enum DelimType {
-    Paren,
-    SquareBracket,
-    CurlyBracket,
-    AngleBracket,
+#[test]
+fn test_check() {
+    let checker = DelimiterChecker::new(&CHUNK_DELIMITERS);
+    assert_eq!(checker.check("([]){<>}"), CheckResult::Valid);
+    assert_eq!(
+        checker.check("{([(<{}[<>[]}>{[]{[(<()>"),
+        CheckResult::Corrupted {
+            position: Position { line: 1, column: 13 },
+            expected: Some(']'),
+            found: '}',
+        },
+    );
+    assert_eq!(
+        checker.check("[({(<(())[]>[[{[]{<()<>>"),
+        CheckResult::Incomplete { completion: "}}]])})]".to_string() },
+    );
+
+    let checker = DelimiterChecker::new(&SOURCE_DELIMITERS);
+    assert_eq!(
+        checker.check("fn main() {\n    x < y)\n}"),
+        CheckResult::Corrupted {
+            position: Position { line: 2, column: 10 },
+            expected: Some('}'),
+            found: ')',
+        },
+    );
+    assert_eq!(
+        checker.check("a)"),
+        CheckResult::Corrupted { position: Position { line: 1, column: 2 }, expected: None, found: ')' },
+    );
+
+    let checker = DelimiterChecker::new(&[('(', ')'), ('"', '"')]);
+    assert_eq!(checker.check(r#"("a" ("b"))"#), CheckResult::Valid);
+    assert_eq!(checker.check(r#""("#), CheckResult::Incomplete { completion: ")\"".to_string() });
+    assert_eq!(
+        checker.check(r#"("a)"#),
+        CheckResult::Corrupted { position: Position { line: 1, column: 4 }, expected: Some('"'), found: ')' },
+    );
 }
 
-fn parse_input(input: &str) -> Vec<Vec<Delim>> {
-    input
-        .lines()
-        .map(|line| line
-             .chars()
-             .map(|c| match c {
-                 '(' => Delim::Open(DelimType::Paren),
-                 ')' => Delim::Close(DelimType::Paren),
-
-                 '[' => Delim::Open(DelimType::SquareBracket),
-                 ']' => Delim::Close(DelimType::SquareBracket),
-
-                 '{' => Delim::Open(DelimType::CurlyBracket),
-                 '}' => Delim::Close(DelimType::CurlyBracket),
-
-                 '<' => Delim::Open(DelimType::AngleBracket),
-                 '>' => Delim::Close(DelimType::AngleBracket),
-
-                 _ => panic!("unknown char {}", c),
-             })
-             .collect()
-        )
-        .collect()
+#[test]
+#[should_panic(expected = "'|' is in both ('|', '|') and ('[', '|')")]
+fn test_ambiguous_delimiters() {
+    DelimiterChecker::new(&[('|', '|'), ('[', '|')]);
 }
 
 const _EXAMPLE: &str = "[({(<(())[]>[[{[]{<()<>>
@@ -1,86 +1,165 @@
+use aoc::matrix::Matrix;
 use itertools::Itertools;
+#[cfg(test)]
 use std::collections::HashMap;
 
 fn main() {
     let input = parse_input(INPUT);
     println!("part1: {}", compute_solution(&input, 10));
     println!("part2: {}", compute_solution(&input, 40));
+
+    // Far too long to count exactly, but fine modulo a prime
+    let steps = 1_000_000_000_000;
+    let modulus = 1_000_000_007;
+    let counts = PolymerRules::new(&input)
+        .element_counts(&input.template, steps, Some(modulus))
+        .expect("modular counts can't overflow");
+    println!(
+        "after {} steps (mod {}): {}",
+        steps,
+        modulus,
+        counts.iter().map(|(element, count)| format!("{}={}", element, count)).join(" ")
+    );
 }
 
-fn compute_solution(input: &Input, steps: usize) -> usize {
-    let counts = polymer_steps_count_pairs(&input.template, &input.rules, steps);
-    let mut sorted_counts = counts
+fn compute_solution(input: &Input, steps: u64) -> u128 {
+    let counts = PolymerRules::new(input)
+        .element_counts(&input.template, steps, None)
+        .expect("element count overflowed");
+    let (min_count, max_count) = counts
         .iter()
-        .map(|(_, &count)| count)
-        .collect::<Vec<usize>>();
-    sorted_counts.sort();
-
-    let max_count = sorted_counts.last().expect("last elem");
-    let min_count = sorted_counts[0];
+        .map(|&(_, count)| count)
+        .filter(|&count| count > 0)
+        .minmax()
+        .into_option()
+        .expect("empty polymer");
     max_count - min_count
 }
 
-fn polymer_steps_count_pairs(start: &[char], rules: &[Rule], num_steps: usize) -> HashMap<char, usize> {
-    let pair_results = rules
-        .iter()
-        .map(|rule| (rule.pair, rule.result))
-        .collect::<HashMap<_, _>>();
+#[test]
+fn test_compute_solution() {
+    let example = parse_input(_EXAMPLE);
+    assert_eq!(compute_solution(&example, 10), 1588);
+    assert_eq!(compute_solution(&example, 40), 2188189693529);
+}
 
-    let mut pair_counts: HashMap<(char, char), usize> = HashMap::new();
-    for (a, b) in start.iter().tuple_windows() {
-        *pair_counts.entry((*a, *b)).or_insert(0) += 1;
-    }
+/// Pair insertion rules as a matrix acting on counts of each pair of
+/// elements, so many steps can be taken at once by raising it to a power.
+struct PolymerRules {
+    elements: Vec<char>,
+    step: Matrix,
+}
+
+impl PolymerRules {
+    fn new(input: &Input) -> Self {
+        let elements: Vec<char> = input
+            .template
+            .iter()
+            .copied()
+            .chain(input.rules.iter().flat_map(|rule| [rule.pair.0, rule.pair.1, rule.result]))
+            .sorted_unstable()
+            .dedup()
+            .collect();
+        let mut rules = PolymerRules { step: Matrix::zero(elements.len().pow(2)), elements };
 
-    for _ in 0..num_steps {
-        let mut new_pair_counts: HashMap<(char, char), usize> = HashMap::new();
-        for (pair, count) in pair_counts {
-            let &replacement = pair_results.get(&pair).expect("couldn't find replacement");
-            *new_pair_counts.entry((pair.0, replacement)).or_insert(0) += count;
-            *new_pair_counts.entry((replacement, pair.1)).or_insert(0) += count;
+        // Pairs without a rule don't change
+        for first in 0..rules.elements.len() {
+            for second in 0..rules.elements.len() {
+                let pair = rules.pair_index(first, second);
+                rules.step.set(pair, pair, 1);
+            }
         }
-        pair_counts = new_pair_counts;
+        for rule in &input.rules {
+            let [first, second, result] =
+                [rule.pair.0, rule.pair.1, rule.result].map(|element| rules.element_index(element));
+            let pair = rules.pair_index(first, second);
+            rules.step.set(pair, pair, 0);
+
+            // Rules like AA -> A produce the same pair twice
+            for new_pair in [rules.pair_index(first, result), rules.pair_index(result, second)] {
+                let count = rules.step.get(new_pair, pair);
+                rules.step.set(new_pair, pair, count + 1);
+            }
+        }
+
+        rules
     }
 
-    // Only count second character for each pair, but add 1 for the
-    // first character (which never changes).
-    let mut char_counts = HashMap::new();
-    char_counts.insert(start[0], 1);
-    for ((_, b), count) in pair_counts {
-        //*char_counts.entry(a).or_insert(0) += count;
-        *char_counts.entry(b).or_insert(0) += count;
+    fn element_index(&self, element: char) -> usize {
+        self.elements
+            .binary_search(&element)
+            .unwrap_or_else(|_| panic!("unknown element {}", element))
+    }
+
+    fn pair_index(&self, first: usize, second: usize) -> usize {
+        first * self.elements.len() + second
+    }
+
+    /// Counts each element in the polymer after some steps, either exactly
+    /// (returning `None` on overflow) or modulo `modulus`.
+    fn element_counts(&self, template: &[char], steps: u64, modulus: Option<u64>) -> Option<Vec<(char, u128)>> {
+        let mut pair_counts = vec![0; self.step.size()];
+        for (a, b) in template.iter().tuple_windows() {
+            pair_counts[self.pair_index(self.element_index(*a), self.element_index(*b))] += 1;
+        }
+        let pair_counts = self.step.pow(steps, modulus)?.mul_vector(&pair_counts, modulus)?;
+
+        // Only count second character for each pair, but add 1 for the
+        // first character (which never changes).
+        let mut counts: Vec<u128> = vec![0; self.elements.len()];
+        if let Some(&first) = template.first() {
+            counts[self.element_index(first)] = 1;
+        }
+        for (pair, count) in pair_counts.into_iter().enumerate() {
+            let second = &mut counts[pair % self.elements.len()];
+            *second = match modulus {
+                Some(m) => (*second + count) % m as u128,
+                None => second.checked_add(count)?,
+            };
+        }
+
+        Some(self.elements.iter().copied().zip(counts).collect())
     }
-    char_counts
 }
 
-// Old, naive solution that rebuild the vector every time
-// fn polymer_steps(start: &[char], rules: &[Rule], num_steps: usize) -> Vec<char> {
-//     let pair_results = rules
-//         .iter()
-//         .map(|rule| (rule.pair, rule.result))
-//         .collect::<HashMap<_, _>>();
-
-//     let mut output = start.to_vec();
-//     for i in 0..num_steps {
-//         println!("i: {}, len: {}", i, output.len());
-//         let mut new_output = vec![output[0]];
-//         for (a, b) in output.iter().tuple_windows() {
-//             let replacement = pair_results.get(&(*a, *b)).expect("couldn't find replacement");
-//             new_output.push(*replacement);
-//             new_output.push(*b);
-//         }
-//         output = new_output;
-//     }
-
-//     output
-// }
-
-// fn char_counts(chars: &[char]) -> HashMap<&char, usize> {
-//     let mut counts = HashMap::new();
-//     for c in chars.iter() {
-//         *counts.entry(c).or_insert(0) += 1;
-//     }
-//     counts
-// }
+#[test]
+fn test_element_counts() {
+    let example = parse_input(_EXAMPLE);
+    let rules = PolymerRules::new(&example);
+    let counts = |steps, modulus| {
+        rules
+            .element_counts(&example.template, steps, modulus)
+            .map(|counts| counts.into_iter().collect::<HashMap<char, u128>>())
+    };
+
+    // NBBNBNBBCCNBCNCCNBBNBBNBBBNBBNBBCBHCBHHNHCBBCBHCB
+    let after_four = counts(4, None).unwrap();
+    assert_eq!(after_four[&'B'], 23);
+    assert_eq!(after_four[&'H'], 5);
+    assert_eq!(after_four.values().sum::<u128>(), 49);
+
+    let exact = counts(40, None).unwrap();
+    let modular = counts(40, Some(1_000_000_007)).unwrap();
+    assert!(exact.iter().all(|(element, count)| modular[element] == count % 1_000_000_007));
+    assert_eq!(counts(200, None), None);
+
+    // Without any rules the polymer stays the same
+    let no_rules = parse_input("NNCB\n");
+    let counts = PolymerRules::new(&no_rules).element_counts(&no_rules.template, 1_000_000_000_000, None);
+    assert_eq!(counts, Some(vec![('B', 1), ('C', 1), ('N', 2)]));
+
+    // Only some pairs have rules, and one rule inserts a copy of its second
+    // element: NNCB -> NCNCCB -> NCCNCCCB
+    let partial = parse_input("NNCB\n\nNN -> C\nNC -> C");
+    let counts = PolymerRules::new(&partial).element_counts(&partial.template, 2, None);
+    assert_eq!(counts, Some(vec![('B', 1), ('C', 5), ('N', 2)]));
+
+    // AA -> A turns each AA into two of them: AAA -> AAAAA -> AAAAAAAAA
+    let repeated = parse_input("AAA\n\nAA -> A");
+    let rules = PolymerRules::new(&repeated);
+    assert_eq!(rules.element_counts(&repeated.template, 2, None), Some(vec![('A', 9)]));
+    assert_eq!(rules.element_counts(&repeated.template, 10, None), Some(vec![('A', 2049)]));
+}
 
 #[derive(Debug)]
 struct Input {
@@ -103,7 +182,7 @@ fn parse_input(input: &str) -> Input {
         .chars()
         .collect::<Vec<char>>();
 
-    assert_eq!(input_lines.next(), Some(""));
+    assert!(matches!(input_lines.next(), Some("") | None));
 
     let rules = input_lines
         .map(|line| {
@@ -2,6 +2,26 @@ fn main() {
     let packet = parse_packet(INPUT);
     println!("part1: {}", version_sum(&packet));
     println!("part2: {}", eval_packet(&packet));
+
+    // Pass an S-expression like "(sum 1 (max 2 3))" on the command
+    // line to see it encoded as a transmission
+    if let Some(expr) = std::env::args().nth(1) {
+        let packet = match parse_sexpr(&expr) {
+            Ok(packet) => packet,
+            Err(err) => {
+                println!("can't parse {}: {}", expr, err);
+                return;
+            }
+        };
+        print!("{}", packet);
+        println!("value: {}", eval_packet(&packet));
+        for (name, length_type) in [("total bits", LengthType::TotalBits), ("sub-packet count", LengthType::SubPacketCount)] {
+            match encode_packet(&packet, length_type) {
+                Ok(encoded) => println!("{}: {}", name, encoded),
+                Err(err) => println!("{}: can't encode: {}", name, err),
+            }
+        }
+    }
 }
 
 fn version_sum(packet: &Packet) -> u64 {
@@ -74,13 +94,13 @@ fn test_eval_packet() {
     assert_eq!(eval_packet(&parse_packet(INPUT)), 246225449979);
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 struct Packet {
     version: u64,
     packet_type: PacketType,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 enum PacketType {
     Literal(u64),
     Operator {
@@ -89,7 +109,7 @@ enum PacketType {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum OpType {
     Sum,
     Product,
@@ -241,16 +261,11 @@ fn parse_literal_packet(bin_chars: &[char], pos: &mut usize) -> PacketType {
 }
 
 fn parse_operator_packet(bin_chars: &[char], pos: &mut usize, op_num: u64) -> PacketType {
-    let op_type = match op_num {
-        0 => OpType::Sum,
-        1 => OpType::Product,
-        2 => OpType::Minimum,
-        3 => OpType::Maximum,
-        5 => OpType::GreaterThan,
-        6 => OpType::LessThan,
-        7 => OpType::EqualTo,
-        _ => panic!("unknown op type {}", op_num),
-    };
+    let op_type = *OP_TYPES
+        .iter()
+        .find(|(num, _, _)| *num == op_num)
+        .map(|(_, op_type, _)| op_type)
+        .unwrap_or_else(|| panic!("unknown op type {}", op_num));
 
     let length_type_id = parse_binary_digit(bin_chars, pos);
     let mut sub_packets = Vec::new();
@@ -278,4 +293,261 @@ fn parse_operator_packet(bin_chars: &[char], pos: &mut usize, op_num: u64) -> Pa
     PacketType::Operator { op_type, sub_packets }
 }
 
+/// Type ID and S-expression name for each operator
+const OP_TYPES: [(u64, OpType, &str); 7] = [
+    (0, OpType::Sum, "sum"),
+    (1, OpType::Product, "product"),
+    (2, OpType::Minimum, "min"),
+    (3, OpType::Maximum, "max"),
+    (5, OpType::GreaterThan, "gt"),
+    (6, OpType::LessThan, "lt"),
+    (7, OpType::EqualTo, "eq"),
+];
+
+fn op_type_entry(op_type: OpType) -> (u64, OpType, &'static str) {
+    *OP_TYPES
+        .iter()
+        .find(|(_, op, _)| *op == op_type)
+        .expect("every op type has an entry")
+}
+
+/// How an operator packet declares the extent of its sub-packets. The
+/// decoded `Packet` doesn't remember which one was used, so the
+/// encoder has to be told.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LengthType {
+    TotalBits,
+    SubPacketCount,
+}
+
+/// Encodes a packet as hexadecimal. Fails if a field doesn't fit in
+/// its bits, like an operator with more sub-packets than its length
+/// field can count.
+fn encode_packet(packet: &Packet, length_type: LengthType) -> Result<String, String> {
+    let mut bin_chars = Vec::new();
+    encode_sub_packet(packet, length_type, &mut bin_chars)?;
+
+    // Pad with zeroes up to a whole number of hexadecimal digits
+    while bin_chars.len() % 4 != 0 {
+        bin_chars.push('0');
+    }
+
+    Ok(bin_chars
+        .chunks(4)
+        .map(|nibble| {
+            let nibble_str = nibble.iter().collect::<String>();
+            let value = u32::from_str_radix(&nibble_str, 2).expect("parse nibble");
+            std::char::from_digit(value, 16).expect("hex digit").to_ascii_uppercase()
+        })
+        .collect())
+}
+
+#[test]
+fn test_encode_packet() {
+    assert_eq!(encode_packet(&parse_packet("D2FE28"), LengthType::TotalBits).unwrap(), "D2FE28");
+
+    // The puzzle examples have more zero padding than we emit
+    assert_eq!(encode_packet(&parse_packet("38006F45291200"), LengthType::TotalBits).unwrap(), "38006F4529120");
+    assert_eq!(encode_packet(&parse_packet("EE00D40C823060"), LengthType::SubPacketCount).unwrap(), "EE00D40C82306");
+
+    // Our encoder might pick a different length type than the
+    // original transmission, so compare decoded packets instead
+    let packet = parse_packet(INPUT);
+    for length_type in [LengthType::TotalBits, LengthType::SubPacketCount] {
+        assert_eq!(parse_packet(&encode_packet(&packet, length_type).unwrap()), packet);
+    }
+
+    // 3000 literals take 33000 bits, too many for either length field
+    let packet = parse_sexpr(&format!("(sum{})", " 1".repeat(3000))).unwrap();
+    assert_eq!(
+        encode_packet(&packet, LengthType::TotalBits),
+        Err("sub-packets take 33000 bits, more than a 15 bit length field holds".to_string()),
+    );
+    assert_eq!(
+        encode_packet(&packet, LengthType::SubPacketCount),
+        Err("3000 sub-packets are more than an 11 bit count field holds".to_string()),
+    );
+}
+
+fn encode_sub_packet(packet: &Packet, length_type: LengthType, bin_chars: &mut Vec<char>) -> Result<(), String> {
+    encode_binary(bin_chars, packet.version, 3)?;
+
+    match &packet.packet_type {
+        PacketType::Literal(value) => {
+            encode_binary(bin_chars, 4, 3)?;
+            encode_literal(bin_chars, *value);
+        }
+        PacketType::Operator { op_type, sub_packets } => {
+            let (op_num, _, _) = op_type_entry(*op_type);
+            encode_binary(bin_chars, op_num, 3)?;
+
+            match length_type {
+                LengthType::TotalBits => {
+                    let mut sub_packet_chars = Vec::new();
+                    for sub_packet in sub_packets {
+                        encode_sub_packet(sub_packet, length_type, &mut sub_packet_chars)?;
+                    }
+                    bin_chars.push('0');
+                    encode_binary(bin_chars, sub_packet_chars.len() as u64, 15).map_err(|_| {
+                        format!("sub-packets take {} bits, more than a 15 bit length field holds", sub_packet_chars.len())
+                    })?;
+                    bin_chars.extend(sub_packet_chars);
+                }
+                LengthType::SubPacketCount => {
+                    bin_chars.push('1');
+                    encode_binary(bin_chars, sub_packets.len() as u64, 11).map_err(|_| {
+                        format!("{} sub-packets are more than an 11 bit count field holds", sub_packets.len())
+                    })?;
+                    for sub_packet in sub_packets {
+                        encode_sub_packet(sub_packet, length_type, bin_chars)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn encode_binary(bin_chars: &mut Vec<char>, num: u64, len: usize) -> Result<(), String> {
+    if len < 64 && num >= (1 << len) {
+        return Err(format!("{} doesn't fit in {} bits", num, len));
+    }
+    for i in (0..len).rev() {
+        bin_chars.push(if (num >> i) & 1 == 1 { '1' } else { '0' });
+    }
+    Ok(())
+}
+
+fn encode_literal(bin_chars: &mut Vec<char>, value: u64) {
+    // Use the fewest four bit groups that hold the value, but always
+    // at least one group
+    let num_groups = (64 - value.leading_zeros() as usize).div_ceil(4);
+    let num_groups = num_groups.max(1);
+
+    for group in (0..num_groups).rev() {
+        bin_chars.push(if group > 0 { '1' } else { '0' });
+        encode_binary(bin_chars, (value >> (group * 4)) & 0xF, 4).expect("masked to four bits");
+    }
+}
+
+/// Parses an S-expression like "(sum 1 (max 2 3))" into a packet.
+/// S-expressions don't have versions, so every packet gets version 0.
+fn parse_sexpr(input: &str) -> Result<Packet, String> {
+    let tokens: Vec<String> = input
+        .replace('(', " ( ")
+        .replace(')', " ) ")
+        .split_whitespace()
+        .map(String::from)
+        .collect();
+
+    let mut pos = 0;
+    let packet = parse_sexpr_tokens(&tokens, &mut pos)?;
+    if pos != tokens.len() {
+        return Err(format!("trailing tokens after S-expression: {}", tokens[pos..].join(" ")));
+    }
+    Ok(packet)
+}
+
+#[test]
+fn test_parse_sexpr() {
+    let packet = parse_sexpr("(sum 1 (max 2 3))").unwrap();
+    assert_eq!(eval_packet(&packet), 4);
+    assert_eq!(parse_packet(&encode_packet(&packet, LengthType::TotalBits).unwrap()), packet);
+
+    assert_eq!(eval_packet(&parse_sexpr("(eq (sum 1 3) (product 2 2))").unwrap()), 1);
+    assert_eq!(eval_packet(&parse_sexpr("42").unwrap()), 42);
+
+    assert_eq!(parse_sexpr(""), Err("unexpected end of S-expression".to_string()));
+    assert_eq!(parse_sexpr("("), Err("expected operator name".to_string()));
+    assert_eq!(parse_sexpr("(sum 1"), Err("expected right paren".to_string()));
+    assert_eq!(parse_sexpr("(avg 1 2)"), Err("unknown operator avg".to_string()));
+    assert_eq!(parse_sexpr("(sum x)"), Err("invalid literal x".to_string()));
+    assert_eq!(parse_sexpr("1 2)"), Err("trailing tokens after S-expression: 2 )".to_string()));
+}
+
+fn parse_sexpr_tokens(tokens: &[String], pos: &mut usize) -> Result<Packet, String> {
+    let token = tokens.get(*pos).ok_or("unexpected end of S-expression")?;
+    *pos += 1;
+
+    if token != "(" {
+        let value = token.parse().map_err(|_| format!("invalid literal {}", token))?;
+        return Ok(Packet { version: 0, packet_type: PacketType::Literal(value) });
+    }
+
+    let name = tokens.get(*pos).ok_or("expected operator name")?;
+    *pos += 1;
+    let (_, op_type, _) = *OP_TYPES
+        .iter()
+        .find(|(_, _, op_name)| op_name == name)
+        .ok_or_else(|| format!("unknown operator {}", name))?;
+
+    let mut sub_packets = Vec::new();
+    while tokens.get(*pos).ok_or("expected right paren")? != ")" {
+        sub_packets.push(parse_sexpr_tokens(tokens, pos)?);
+    }
+    *pos += 1;
+
+    Ok(Packet { version: 0, packet_type: PacketType::Operator { op_type, sub_packets } })
+}
+
+impl Packet {
+    fn fmt_indented(&self, f: &mut std::fmt::Formatter, depth: usize) -> std::fmt::Result {
+        let indent = "  ".repeat(depth);
+        match &self.packet_type {
+            PacketType::Literal(value) => writeln!(f, "{}v{} {}", indent, self.version, value),
+            PacketType::Operator { op_type, sub_packets } => {
+                let (_, _, name) = op_type_entry(*op_type);
+                writeln!(f, "{}v{} {}", indent, self.version, name)?;
+                for sub_packet in sub_packets {
+                    sub_packet.fmt_indented(f, depth + 1)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Packet {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+#[test]
+fn test_display_packet() {
+    assert_eq!(
+        parse_packet("EE00D40C823060").to_string(),
+        "v7 max\n  v2 1\n  v4 2\n  v1 3\n",
+    );
+}
+
+#[cfg(test)]
+fn arb_packet() -> impl proptest::strategy::Strategy<Value = Packet> {
+    use proptest::prelude::*;
+
+    let literal = (0..8u64, any::<u64>()).prop_map(|(version, value)| Packet {
+        version,
+        packet_type: PacketType::Literal(value),
+    });
+
+    literal.prop_recursive(4, 64, 5, |inner| {
+        (0..8u64, 0..OP_TYPES.len(), prop::collection::vec(inner, 0..5)).prop_map(
+            |(version, op_index, sub_packets)| Packet {
+                version,
+                packet_type: PacketType::Operator { op_type: OP_TYPES[op_index].1, sub_packets },
+            },
+        )
+    })
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn test_encode_round_trip(packet in arb_packet()) {
+        for length_type in [LengthType::TotalBits, LengthType::SubPacketCount] {
+            proptest::prop_assert_eq!(parse_packet(&encode_packet(&packet, length_type).unwrap()), packet.clone());
+        }
+    }
+}
+
 const INPUT: &str = "6053231004C12DC26D00526BEE728D2C013AC7795ACA756F93B524D8000AAC8FF80B3A7A4016F6802D35C7C94C8AC97AD81D30024C00D1003C80AD050029C00E20240580853401E98C00D50038400D401518C00C7003880376300290023000060D800D09B9D03E7F546930052C016000422234208CC000854778CF0EA7C9C802ACE005FE4EBE1B99EA4C8A2A804D26730E25AA8B23CBDE7C855808057C9C87718DFEED9A008880391520BC280004260C44C8E460086802600087C548430A4401B8C91AE3749CF9CEFF0A8C0041498F180532A9728813A012261367931FF43E9040191F002A539D7A9CEBFCF7B3DE36CA56BC506005EE6393A0ACAA990030B3E29348734BC200D980390960BC723007614C618DC600D4268AD168C0268ED2CB72E09341040181D802B285937A739ACCEFFE9F4B6D30802DC94803D80292B5389DFEB2A440081CE0FCE951005AD800D04BF26B32FC9AFCF8D280592D65B9CE67DCEF20C530E13B7F67F8FB140D200E6673BA45C0086262FBB084F5BF381918017221E402474EF86280333100622FC37844200DC6A8950650005C8273133A300465A7AEC08B00103925392575007E63310592EA747830052801C99C9CB215397F3ACF97CFE41C802DBD004244C67B189E3BC4584E2013C1F91B0BCD60AA1690060360094F6A70B7FC7D34A52CBAE011CB6A17509F8DF61F3B4ED46A683E6BD258100667EA4B1A6211006AD367D600ACBD61FD10CBD61FD129003D9600B4608C931D54700AA6E2932D3CBB45399A49E66E641274AE4040039B8BD2C933137F95A4A76CFBAE122704026E700662200D4358530D4401F8AD0722DCEC3124E92B639CC5AF413300700010D8F30FE1B80021506A33C3F1007A314348DC0002EC4D9CF36280213938F648925BDE134803CB9BD6BF3BFD83C0149E859EA6614A8C";
@@ -1,82 +1,236 @@
+use std::cmp::max;
+
 fn main() {
-    let target = INPUT;
+    let target = parse_target(INPUT);
+    let velocities = valid_velocities(&target).expect("infinitely many velocities hit the target");
 
-    let part1 = (target.ymin) * (target.ymin + 1) / 2;
+    let part1 = velocities.iter().map(|&(_, vy)| highest_y(vy)).max().expect("no velocity hits the target");
     println!("part1: {}", part1);
 
-    let vx_min = 1;
-    let vx_max = 500;
-    let vy_min = -300;
-    let vy_max = 1000;
-    let part2 = count_reachable_velocities(&target, vx_min, vx_max, vy_min, vy_max);
-    println!("part1: {}", part2);
+    let part2 = velocities.len();
+    println!("part2: {}", part2);
+}
+
+#[derive(Debug, PartialEq)]
+struct Target {
+    xmin: i64,
+    xmax: i64,
+    ymin: i64,
+    ymax: i64,
+}
+
+fn parse_target(input: &str) -> Target {
+    let (xs, ys) = input
+        .trim()
+        .strip_prefix("target area: x=")
+        .and_then(|rest| rest.split_once(", y="))
+        .expect("expected 'target area: x=..., y=...'");
+    let (xmin, xmax) = parse_range(xs);
+    let (ymin, ymax) = parse_range(ys);
+    Target { xmin, xmax, ymin, ymax }
 }
 
-fn reaches_target(vx: u32, vy: i32, target: &Target) -> bool {
-    let mut vx = vx;
-    let mut vy = vy;
-    let mut x: u32 = 0;
-    let mut y: i32 = 0;
+fn parse_range(input: &str) -> (i64, i64) {
+    let (lo, hi) = input.split_once("..").expect("expected range with '..'");
+    let lo = lo.parse().expect("parse range start");
+    let hi = hi.parse().expect("parse range end");
+    (std::cmp::min(lo, hi), max(lo, hi))
+}
 
-    loop {
+#[test]
+fn test_parse_target() {
+    assert_eq!(
+        parse_target(_EXAMPLE),
+        Target { xmin: 20, xmax: 30, ymin: -10, ymax: -5 }
+    );
+}
+
+fn highest_y(vy: i64) -> i64 {
+    max(vy, 0) * (max(vy, 0) + 1) / 2
+}
+
+/// A run of consecutive steps where the probe is within the target along
+/// one axis. A `last` of `None` means the probe stays there forever.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct StepWindow {
+    first: i64,
+    last: Option<i64>,
+}
+
+impl StepWindow {
+    fn overlaps(&self, other: &StepWindow) -> bool {
+        self.first <= other.last.unwrap_or(i64::MAX) && other.first <= self.last.unwrap_or(i64::MAX)
+    }
+}
+
+fn add_step(windows: &mut Vec<StepWindow>, step: i64, forever: bool) {
+    let last = if forever { None } else { Some(step) };
+    match windows.last_mut() {
+        Some(window) if window.last == Some(step - 1) => window.last = last,
+        _ => windows.push(StepWindow { first: step, last }),
+    }
+}
+
+fn x_windows(vx: i64, xmin: i64, xmax: i64) -> Vec<StepWindow> {
+    // Moving left is the mirror image of moving right
+    if vx < 0 {
+        return x_windows(-vx, -xmax, -xmin);
+    }
+
+    let mut windows = Vec::new();
+    let (mut x, mut vx) = (0, vx);
+    for step in 1.. {
         x += vx;
-        y += vy;
+        vx = max(vx - 1, 0);
 
-        if y < target.ymin || x > target.xmax {
-            return false;
+        // Once drag stops the probe, x never changes again
+        let inside = x >= xmin && x <= xmax;
+        if inside {
+            add_step(&mut windows, step, vx == 0);
         }
-        if target.contains(x, y) {
-            return true;
+        if vx == 0 || x > xmax {
+            break;
         }
+    }
+    windows
+}
+
+#[test]
+fn test_x_windows() {
+    assert_eq!(x_windows(7, 20, 30), vec![StepWindow { first: 4, last: None }]);
+    assert_eq!(x_windows(30, 20, 30), vec![StepWindow { first: 1, last: Some(1) }]);
+    assert_eq!(x_windows(-30, -30, -20), vec![StepWindow { first: 1, last: Some(1) }]);
+    assert_eq!(x_windows(31, 20, 30), vec![]);
+    assert_eq!(x_windows(0, -1, 1), vec![StepWindow { first: 1, last: None }]);
+}
 
-        vx = vx.saturating_sub(1);
+fn y_windows(vy: i64, ymin: i64, ymax: i64) -> Vec<StepWindow> {
+    // A target above the origin can be passed through once going up and
+    // again coming down, so there may be two windows
+    let mut windows = Vec::new();
+    let (mut y, mut vy) = (0, vy);
+    for step in 1.. {
+        y += vy;
         vy -= 1;
+
+        if y >= ymin && y <= ymax {
+            add_step(&mut windows, step, false);
+        }
+        if vy < 0 && y < ymin {
+            break;
+        }
     }
+    windows
+}
+
+#[test]
+fn test_y_windows() {
+    assert_eq!(y_windows(2, -10, -5), vec![StepWindow { first: 7, last: Some(7) }]);
+    assert_eq!(y_windows(-10, -10, -5), vec![StepWindow { first: 1, last: Some(1) }]);
+    assert_eq!(
+        y_windows(5, 8, 9),
+        vec![StepWindow { first: 2, last: Some(2) }, StepWindow { first: 9, last: Some(9) }]
+    );
 }
 
-fn count_reachable_velocities(target: &Target, vx_min: u32, vx_max: u32, vy_min: i32, vy_max: i32) -> usize {
-    let mut reachable = 0;
-    for vx in vx_min..=vx_max {
-        for vy in vy_min..=vy_max {
-            if reaches_target(vx, vy, target) {
-                reachable += 1;
+/// Returns every initial velocity that puts the probe inside the target
+/// after some step, or `None` if there are infinitely many.
+fn valid_velocities(target: &Target) -> Option<Vec<(i64, i64)>> {
+    // Any faster and the probe overshoots the target on the first step
+    let vx_range = std::cmp::min(target.xmin, 0)..=max(target.xmax, 0);
+    let vx_windows: Vec<(i64, Vec<StepWindow>)> = vx_range
+        .map(|vx| (vx, x_windows(vx, target.xmin, target.xmax)))
+        .filter(|(_, windows)| !windows.is_empty())
+        .collect();
+
+    // Moving up, the probe comes back down through y=0 with a speed one
+    // more than it started with, so it skips over any target below if it
+    // is thrown too hard. A target above is overshot if the first step
+    // is already past it. Targets straddling y=0 are hit on the way back
+    // down through y=0, so the bound is how long x stays inside.
+    let mut vy_max = max(target.ymax, -target.ymin - 1);
+    if target.ymin <= 0 && target.ymax >= 0 {
+        let mut last_step = 0;
+        for (_, windows) in &vx_windows {
+            for window in windows {
+                last_step = max(last_step, window.last?);
             }
         }
+        vy_max = max(vy_max, last_step / 2);
     }
-    reachable
+    let vy_min = std::cmp::min(target.ymin, 0);
+
+    let vy_windows: Vec<(i64, Vec<StepWindow>)> = (vy_min..=vy_max)
+        .map(|vy| (vy, y_windows(vy, target.ymin, target.ymax)))
+        .filter(|(_, windows)| !windows.is_empty())
+        .collect();
+
+    let mut velocities = Vec::new();
+    for (vx, xs) in &vx_windows {
+        for (vy, ys) in &vy_windows {
+            if xs.iter().any(|x| ys.iter().any(|y| x.overlaps(y))) {
+                velocities.push((*vx, *vy));
+            }
+        }
+    }
+    Some(velocities)
 }
 
 #[test]
-fn test_reaches_target() {
-    assert!(reaches_target(7, 2, &_EXAMPLE));
-    assert!(reaches_target(6, 3, &_EXAMPLE));
-    assert!(reaches_target(9, 0, &_EXAMPLE));
-    assert!(!reaches_target(17, -4, &_EXAMPLE));
-}
+fn test_valid_velocities() {
+    let velocities = valid_velocities(&parse_target(_EXAMPLE)).unwrap();
+    assert_eq!(velocities.len(), 112);
+    assert_eq!(velocities.iter().map(|&(_, vy)| highest_y(vy)).max(), Some(45));
 
-struct Target {
-    xmin: u32,
-    xmax: u32,
-    ymin: i32,
-    ymax: i32,
+    // The same target mirrored to the left of the origin
+    let velocities = valid_velocities(&parse_target("target area: x=-30..-20, y=-10..-5")).unwrap();
+    assert_eq!(velocities.len(), 112);
+
+    // A probe that stops inside a target straddling y=0 hits it with any
+    // upwards velocity
+    assert_eq!(valid_velocities(&parse_target("target area: x=20..30, y=-5..5")), None);
+
+    for input in [
+        "target area: x=20..30, y=-10..-5",
+        "target area: x=-5..12, y=-10..-5",
+        "target area: x=20..30, y=5..10",
+        "target area: x=-30..-20, y=15..40",
+        "target area: x=7..9, y=-2..2",
+        "target area: x=-8..-7, y=-3..4",
+    ] {
+        let target = parse_target(input);
+        let mut expected = Vec::new();
+        for vx in -50..=50 {
+            for vy in -50..=100 {
+                if reaches_target(vx, vy, &target) {
+                    expected.push((vx, vy));
+                }
+            }
+        }
+        assert_eq!(valid_velocities(&target), Some(expected), "{}", input);
+    }
 }
 
-impl Target {
-    fn contains(&self, x: u32, y: i32) -> bool {
-        x >= self.xmin && x <= self.xmax && y >= self.ymin && y <= self.ymax
+/// Simulates every step, for checking `valid_velocities`.
+#[cfg(test)]
+fn reaches_target(vx: i64, vy: i64, target: &Target) -> bool {
+    let (mut x, mut y) = (0, 0);
+    let (mut vx, mut vy) = (vx, vy);
+
+    for _ in 0..1000 {
+        x += vx;
+        y += vy;
+
+        if x >= target.xmin && x <= target.xmax && y >= target.ymin && y <= target.ymax {
+            return true;
+        }
+
+        vx -= vx.signum();
+        vy -= 1;
     }
+    false
 }
 
-const _EXAMPLE: Target = Target {
-    xmin: 20,
-    xmax: 30,
-    ymin: -10,
-    ymax: -5,
-};
+const _EXAMPLE: &str = "target area: x=20..30, y=-10..-5";
 
-const INPUT: Target = Target {
-    xmin: 153,
-    xmax: 199,
-    ymin: -114,
-    ymax: -75,
-};
+const INPUT: &str = "target area: x=153..199, y=-114..-75";
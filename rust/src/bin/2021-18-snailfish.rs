@@ -1,22 +1,69 @@
-fn main() {}
+use std::fmt;
+use std::ops::Add;
 
+fn main() {
+    let pairs = parse_input(INPUT);
+    println!("part1: {}", part1_sum_magnitude(&pairs));
+    println!("part2: {}", part2_max_pair_magnitude(&pairs));
+}
 
-// fn reduce(pair: &Pair) -> Pair {
+fn part1_sum_magnitude(pairs: &[Pair]) -> u32 {
+    sum_pairs(pairs).magnitude()
+}
 
-// }
+fn part2_max_pair_magnitude(pairs: &[Pair]) -> u32 {
+    // Snailfish addition isn't commutative, so try both orders
+    let mut max_magnitude = 0;
+    for (i, x) in pairs.iter().enumerate() {
+        for (j, y) in pairs.iter().enumerate() {
+            if i != j {
+                max_magnitude = max_magnitude.max((x.clone() + y.clone()).magnitude());
+            }
+        }
+    }
+    max_magnitude
+}
 
-/// Simpler representation for the problem. Array of (depth, num).
-// type PairArray = Vec<(u8, u32)>;
+#[test]
+fn test_homework() {
+    let pairs = parse_input(_EXAMPLE);
+    assert_eq!(
+        sum_pairs(&pairs).to_string(),
+        "[[[[6,6],[7,6]],[[7,7],[7,0]]],[[[7,7],[7,7]],[[7,8],[9,9]]]]",
+    );
+    assert_eq!(part1_sum_magnitude(&pairs), 4140);
+    assert_eq!(part2_max_pair_magnitude(&pairs), 3993);
+}
 
-// fn pair_to_array(pair: &Pair) -> PairArray {
-//     let mut array = Vec::new();
+fn sum_pairs(pairs: &[Pair]) -> Pair {
+    pairs
+        .iter()
+        .cloned()
+        .reduce(|acc, pair| acc + pair)
+        .expect("no pairs to sum")
+}
 
-//     array
-// }
+#[test]
+fn test_sum_pairs() {
+    let sum = |input: &str| sum_pairs(&parse_input(input)).to_string();
 
-// fn array_to_pair(array: &PairArray) -> Pair {
-//     todo!()
-// }
+    assert_eq!(sum("[1,1]\n[2,2]\n[3,3]\n[4,4]"), "[[[[1,1],[2,2]],[3,3]],[4,4]]");
+    assert_eq!(sum("[1,1]\n[2,2]\n[3,3]\n[4,4]\n[5,5]"), "[[[[3,0],[5,3]],[4,4]],[5,5]]");
+    assert_eq!(sum("[1,1]\n[2,2]\n[3,3]\n[4,4]\n[5,5]\n[6,6]"), "[[[[5,0],[7,4]],[5,5]],[6,6]]");
+    assert_eq!(
+        sum("[[[0,[4,5]],[0,0]],[[[4,5],[2,6]],[9,5]]]
+[7,[[[3,7],[4,3]],[[6,3],[8,8]]]]
+[[2,[[0,8],[3,4]]],[[[6,7],1],[7,[1,6]]]]
+[[[[2,4],7],[6,[0,5]]],[[[6,8],[2,8]],[[2,1],[4,5]]]]
+[7,[5,[[3,8],[1,4]]]]
+[[2,[2,2]],[8,[8,1]]]
+[2,9]
+[1,[[[9,3],9],[[9,0],[0,7]]]]
+[[[5,[7,4]],7],1]
+[[[[4,2],2],6],[8,7]]"),
+        "[[[[8,7],[7,7]],[[8,6],[7,7]]],[[[0,7],[6,6]],[8,7]]]",
+    );
+}
 
 #[derive(Debug, PartialEq, Clone)]
 struct Pair {
@@ -25,26 +72,18 @@ struct Pair {
 }
 
 impl Pair {
-    fn reduce(&self) -> Pair {
-        let mut pair = self.clone();
-
+    fn reduce(mut self) -> Pair {
         loop {
             if self.explode_leftmost() {
                 continue;
             }
 
-            // If any regular number is 10 or greater, the leftmost
-            // such regular number splits. To split a regular number,
-            // replace it with a pair; the left element of the pair
-            // should be the regular number divided by two and rounded
-            // down, while the right element of the pair should be the
-            // regular number divided by two and rounded up. For
-            // example, 10 becomes [5,5], 11 becomes [5,6], 12 becomes
-            // [6,6], and so on.
-
+            if self.split_leftmost() {
+                continue;
+            }
 
             // No rule applied, return the new pair
-            return pair
+            return self;
         }
     }
 
@@ -56,23 +95,117 @@ impl Pair {
     /// any). Exploding pairs will always consist of two regular
     /// numbers. Then, the entire exploding pair is replaced with the
     /// regular number 0.
-    fn explode_leftmost(&self) -> (Pair, bool) {
-        let (new_pair, lval, rval, changed) = self.explode_leftmost_inner(1);
-        assert!(lval.is_none());
-        assert!(rval.is_none());
-        (new_pair, changed)
+    fn explode_leftmost(&mut self) -> bool {
+        self.explode_leftmost_inner(1).is_some()
     }
 
-    fn explode_leftmost_inner(&self, level: usize) -> (Pair, Option<u32>, Option<u32>, bool) {
-        match (self.lhs, self.rhs) {
-            (PairElement::Num(x), PairElement::Num(y)) => {
-                if level > 4 {
-                    return
-                }
-            },
-            _ => {},
+    /// Returns `None` if nothing exploded. Otherwise returns the parts
+    /// of the exploded pair that still need to be added to a regular
+    /// number to the left and right of this pair.
+    fn explode_leftmost_inner(&mut self, level: usize) -> Option<(Option<u32>, Option<u32>)> {
+        if let Some((lval, rval)) = self.lhs.explode_leftmost_inner(level + 1) {
+            if let Some(rval) = rval {
+                self.rhs.add_leftmost(rval);
+            }
+            return Some((lval, None));
+        }
+
+        if let Some((lval, rval)) = self.rhs.explode_leftmost_inner(level + 1) {
+            if let Some(lval) = lval {
+                self.lhs.add_rightmost(lval);
+            }
+            return Some((None, rval));
+        }
+
+        None
+    }
+
+    /// If any regular number is 10 or greater, the leftmost such
+    /// regular number splits. To split a regular number, replace it
+    /// with a pair; the left element of the pair should be the
+    /// regular number divided by two and rounded down, while the
+    /// right element of the pair should be the regular number divided
+    /// by two and rounded up. For example, 10 becomes [5,5], 11
+    /// becomes [5,6], 12 becomes [6,6], and so on.
+    fn split_leftmost(&mut self) -> bool {
+        self.lhs.split_leftmost() || self.rhs.split_leftmost()
+    }
+
+    /// The magnitude of a pair is 3 times the magnitude of its left
+    /// element plus 2 times the magnitude of its right element. The
+    /// magnitude of a regular number is just that number.
+    fn magnitude(&self) -> u32 {
+        3 * self.lhs.magnitude() + 2 * self.rhs.magnitude()
+    }
+}
+
+#[test]
+fn test_explode_leftmost() {
+    let explode = |input: &str| {
+        let mut pair = parse_pair(input);
+        assert!(pair.explode_leftmost());
+        pair.to_string()
+    };
+
+    assert_eq!(explode("[[[[[9,8],1],2],3],4]"), "[[[[0,9],2],3],4]");
+    assert_eq!(explode("[7,[6,[5,[4,[3,2]]]]]"), "[7,[6,[5,[7,0]]]]");
+    assert_eq!(explode("[[6,[5,[4,[3,2]]]],1]"), "[[6,[5,[7,0]]],3]");
+    assert_eq!(explode("[[3,[2,[1,[7,3]]]],[6,[5,[4,[3,2]]]]]"), "[[3,[2,[8,0]]],[9,[5,[4,[3,2]]]]]");
+    assert_eq!(explode("[[3,[2,[8,0]]],[9,[5,[4,[3,2]]]]]"), "[[3,[2,[8,0]]],[9,[5,[7,0]]]]");
+
+    assert!(!parse_pair("[[[[0,9],2],3],4]").explode_leftmost());
+}
+
+#[test]
+fn test_reduce_steps() {
+    // Step by step reduction from the puzzle text
+    let mut pair = parse_pair("[[[[[4,3],4],4],[7,[[8,4],9]]],[1,1]]");
+
+    assert!(pair.explode_leftmost());
+    assert_eq!(pair.to_string(), "[[[[0,7],4],[7,[[8,4],9]]],[1,1]]");
+    assert!(pair.explode_leftmost());
+    assert_eq!(pair.to_string(), "[[[[0,7],4],[15,[0,13]]],[1,1]]");
+    assert!(!pair.explode_leftmost());
+    assert!(pair.split_leftmost());
+    assert_eq!(pair.to_string(), "[[[[0,7],4],[[7,8],[0,13]]],[1,1]]");
+    assert!(pair.split_leftmost());
+    assert_eq!(pair.to_string(), "[[[[0,7],4],[[7,8],[0,[6,7]]]],[1,1]]");
+    assert!(pair.explode_leftmost());
+    assert_eq!(pair.to_string(), "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]");
+
+    assert_eq!(
+        parse_pair("[[[[4,3],4],4],[7,[[8,4],9]]]") + parse_pair("[1,1]"),
+        parse_pair("[[[[0,7],4],[[7,8],[6,0]]],[8,1]]"),
+    );
+}
+
+#[test]
+fn test_magnitude() {
+    assert_eq!(parse_pair("[[1,2],[[3,4],5]]").magnitude(), 143);
+    assert_eq!(parse_pair("[[[[0,7],4],[[7,8],[6,0]]],[8,1]]").magnitude(), 1384);
+    assert_eq!(parse_pair("[[[[1,1],[2,2]],[3,3]],[4,4]]").magnitude(), 445);
+    assert_eq!(parse_pair("[[[[3,0],[5,3]],[4,4]],[5,5]]").magnitude(), 791);
+    assert_eq!(parse_pair("[[[[5,0],[7,4]],[5,5]],[6,6]]").magnitude(), 1137);
+    assert_eq!(parse_pair("[[[[8,7],[7,7]],[[8,6],[7,7]]],[[[0,7],[6,6]],[8,7]]]").magnitude(), 3488);
+}
+
+impl Add for Pair {
+    type Output = Pair;
+
+    /// To add two snailfish numbers, form a pair from the left and
+    /// right parameters of the addition operator, then reduce it.
+    fn add(self, other: Pair) -> Pair {
+        Pair {
+            lhs: PairElement::Pair(Box::new(self)),
+            rhs: PairElement::Pair(Box::new(other)),
         }
-        //self.lhs.explode_leftmost_inner(level) || self.rhs.explode_leftmost_inner(level)
+        .reduce()
+    }
+}
+
+impl fmt::Display for Pair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{},{}]", self.lhs, self.rhs)
     }
 }
 
@@ -82,6 +215,73 @@ enum PairElement {
     Pair(Box<Pair>),
 }
 
+impl PairElement {
+    fn explode_leftmost_inner(&mut self, level: usize) -> Option<(Option<u32>, Option<u32>)> {
+        let pair = match self {
+            PairElement::Num(_) => return None,
+            PairElement::Pair(pair) => pair,
+        };
+
+        if level > 4 {
+            if let (PairElement::Num(x), PairElement::Num(y)) = (&pair.lhs, &pair.rhs) {
+                let exploded = (Some(*x), Some(*y));
+                *self = PairElement::Num(0);
+                return Some(exploded);
+            }
+        }
+
+        pair.explode_leftmost_inner(level)
+    }
+
+    fn add_leftmost(&mut self, val: u32) {
+        match self {
+            PairElement::Num(x) => *x += val,
+            PairElement::Pair(pair) => pair.lhs.add_leftmost(val),
+        }
+    }
+
+    fn add_rightmost(&mut self, val: u32) {
+        match self {
+            PairElement::Num(x) => *x += val,
+            PairElement::Pair(pair) => pair.rhs.add_rightmost(val),
+        }
+    }
+
+    fn split_leftmost(&mut self) -> bool {
+        match self {
+            PairElement::Num(x) if *x >= 10 => {
+                *self = PairElement::Pair(Box::new(Pair {
+                    lhs: PairElement::Num(*x / 2),
+                    rhs: PairElement::Num(x.div_ceil(2)),
+                }));
+                true
+            }
+            PairElement::Num(_) => false,
+            PairElement::Pair(pair) => pair.split_leftmost(),
+        }
+    }
+
+    fn magnitude(&self) -> u32 {
+        match self {
+            PairElement::Num(x) => *x,
+            PairElement::Pair(pair) => pair.magnitude(),
+        }
+    }
+}
+
+impl fmt::Display for PairElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PairElement::Num(x) => write!(f, "{}", x),
+            PairElement::Pair(pair) => write!(f, "{}", pair),
+        }
+    }
+}
+
+fn parse_input(input: &str) -> Vec<Pair> {
+    input.lines().map(parse_pair).collect()
+}
+
 fn parse_pair(line: &str) -> Pair {
     let chars = line.chars().collect::<Vec<char>>();
     let mut i = 0;
@@ -108,14 +308,26 @@ fn parse_pair_inner(chars: &[char], i: &mut usize) -> Pair {
 }
 
 fn parse_pair_element(chars: &[char], i: &mut usize) -> PairElement {
-    match chars[*i] {
-        '[' => PairElement::Pair(Box::new(parse_pair_inner(chars, i))),
-        c => {
-            // Assume num. Also, all input numbers are a single character
-            *i += 1;
-            PairElement::Num(c.to_digit(10).expect("pair element digit"))
-        }
+    if chars[*i] == '[' {
+        return PairElement::Pair(Box::new(parse_pair_inner(chars, i)));
+    }
+
+    // Input numbers are a single digit, but partially reduced numbers
+    // can have larger regular numbers
+    let mut num = 0;
+    while let Some(digit) = chars[*i].to_digit(10) {
+        num = num * 10 + digit;
+        *i += 1;
     }
+    PairElement::Num(num)
+}
+
+#[test]
+fn test_display_round_trip() {
+    for line in INPUT.lines().chain(_EXAMPLE.lines()) {
+        assert_eq!(parse_pair(line).to_string(), line);
+    }
+    assert_eq!(parse_pair("[[15,[0,13]],1]").to_string(), "[[15,[0,13]],1]");
 }
 
 #[test]
@@ -258,3 +470,14 @@ const INPUT: &str = "[[[[9,5],[9,4]],[[6,5],[7,0]]],4]
 [[4,[[7,5],8]],[[4,0],[[6,6],[0,4]]]]
 [[[9,[7,7]],[[4,2],7]],4]
 [[0,[0,3]],5]";
+
+const _EXAMPLE: &str = "[[[0,[5,8]],[[1,7],[9,6]]],[[4,[1,2]],[[1,4],2]]]
+[[[5,[2,8]],4],[5,[[9,9],0]]]
+[6,[[[6,2],[5,6]],[[7,6],[4,7]]]]
+[[[6,[0,7]],[0,9]],[4,[9,[9,0]]]]
+[[[7,[6,4]],[3,[1,3]]],[[[5,5],1],9]]
+[[6,[[7,3],[3,2]]],[[[3,8],[5,7]],4]]
+[[[[5,4],[7,7]],8],[[8,3],8]]
+[[9,3],[[9,9],[6,[4,9]]]]
+[[2,[[7,7],7]],[[5,8],[[9,3],[0,2]]]]
+[[[[5,2],5],[8,[3,7]]],[[5,[7,5]],[4,4]]]";
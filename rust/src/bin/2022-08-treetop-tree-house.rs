@@ -1,16 +1,155 @@
 fn main() {
     let input = parse_input(INPUT);
-    println!("part 1: {}", part1_num_visible(&input));
-    println!("part 2: {}", part2_max_scenic_score(&input));
+    let view = ForestView::new(&input);
+    println!("part 1: {}", view.num_visible());
+    println!("part 2: {}", view.max_scenic_score());
+
+    // Pass a path on the command line to save the scenic scores as an image
+    if let Some(path) = std::env::args().nth(1) {
+        std::fs::write(&path, view.scenic_heatmap_pgm())
+            .unwrap_or_else(|err| panic!("couldn't write {}: {}", path, err));
+    }
 }
 
-fn part1_num_visible(trees: &[Vec<u32>]) -> usize {
-    visibility_map(trees)
-        .into_iter()
-        .map(|row| row.into_iter().filter(|vis| *vis).count())
-        .sum()
+/// What can be seen from each tree, and which trees can be seen from
+/// outside the forest.
+struct ForestView {
+    visible: Vec<Vec<bool>>,
+    scenic_scores: Vec<Vec<u64>>,
 }
 
+impl ForestView {
+    fn new(trees: &[Vec<u32>]) -> Self {
+        let mut view = ForestView {
+            visible: trees.iter().map(|row| vec![false; row.len()]).collect(),
+            scenic_scores: trees.iter().map(|row| vec![1; row.len()]).collect(),
+        };
+
+        let num_rows = trees.len();
+        let num_cols = trees.first().map_or(0, |row| row.len());
+        for i in 0..num_rows {
+            // Looking left, then right
+            view.look_back_along((0..num_cols).map(|j| (i, j)), trees);
+            view.look_back_along((0..num_cols).rev().map(|j| (i, j)), trees);
+        }
+        for j in 0..num_cols {
+            // Looking up, then down
+            view.look_back_along((0..num_rows).map(|i| (i, j)), trees);
+            view.look_back_along((0..num_rows).rev().map(|i| (i, j)), trees);
+        }
+
+        view
+    }
+
+    /// Updates each tree in a line with what it sees looking back towards
+    /// the start of the line. A stack holds the trees that could still
+    /// block the view of later trees, so they get shorter towards the top
+    /// and each tree is pushed and popped at most once.
+    fn look_back_along<I>(&mut self, indices: I, trees: &[Vec<u32>])
+    where
+        I: IntoIterator<Item = (usize, usize)>,
+    {
+        let mut blockers: Vec<(usize, u32)> = Vec::new();
+        for (position, (i, j)) in indices.into_iter().enumerate() {
+            let height = trees[i][j];
+            while blockers.last().is_some_and(|&(_, blocker)| blocker < height) {
+                blockers.pop();
+            }
+
+            // Without anything as tall in the way, the tree can see (and be
+            // seen from) the edge
+            let distance = match blockers.last() {
+                Some(&(blocker_position, _)) => position - blocker_position,
+                None => {
+                    self.visible[i][j] = true;
+                    position
+                }
+            };
+            self.scenic_scores[i][j] *= distance as u64;
+
+            blockers.push((position, height));
+        }
+    }
+
+    fn num_visible(&self) -> usize {
+        self.visible.iter().flatten().filter(|vis| **vis).count()
+    }
+
+    fn max_scenic_score(&self) -> u64 {
+        self.scenic_scores.iter().flatten().copied().max().expect("no scenic score at all")
+    }
+
+    /// Renders scenic scores as a binary PGM image, with one pixel per tree
+    /// and the best score in white.
+    fn scenic_heatmap_pgm(&self) -> Vec<u8> {
+        let height = self.scenic_scores.len();
+        let width = self.scenic_scores.first().map_or(0, |row| row.len());
+        let best = self.scenic_scores.iter().flatten().copied().max().unwrap_or(0).max(1);
+
+        let mut image = format!("P5\n{} {}\n255\n", width, height).into_bytes();
+        for score in self.scenic_scores.iter().flatten() {
+            image.push((*score * 255 / best) as u8);
+        }
+        image
+    }
+}
+
+#[test]
+fn test_forest_view() {
+    let view = ForestView::new(&parse_input(_EXAMPLE));
+    assert_eq!(view.num_visible(), 21);
+    assert_eq!(view.max_scenic_score(), 8);
+    assert_eq!(view.scenic_scores[1][2], 4);
+    assert_eq!(view.scenic_scores[3][2], 8);
+
+    let pgm = view.scenic_heatmap_pgm();
+    assert!(pgm.starts_with(b"P5\n5 5\n255\n"));
+    assert_eq!(pgm.len(), 11 + 25);
+    assert_eq!(pgm[11 + 3 * 5 + 2], 255);
+    assert_eq!(pgm[11 + 5 + 2], 127);
+}
+
+#[test]
+fn test_forest_view_matches_scanning() {
+    // Pseudo-random forests with lots of equal heights
+    let mut seed: u64 = 12345;
+    let mut forests = vec![parse_input(INPUT), vec![vec![5]], vec![vec![1, 2, 3]]];
+    for size in 2..30 {
+        let forest = (0..size)
+            .map(|_| {
+                (0..size + 3)
+                    .map(|_| {
+                        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                        (seed >> 60) as u32 % 10
+                    })
+                    .collect()
+            })
+            .collect();
+        forests.push(forest);
+    }
+
+    // A tall tree in the middle of a big flat forest, whose score doesn't
+    // fit in a u32
+    let mut big = vec![vec![0; 1001]; 1001];
+    big[500][500] = 9;
+    assert_eq!(ForestView::new(&big).max_scenic_score(), 500u64.pow(4));
+    forests.push(big);
+
+    for trees in &forests {
+        let view = ForestView::new(trees);
+        assert_eq!(view.visible, visibility_map(trees));
+        for (i, row) in trees.iter().enumerate() {
+            for j in 0..row.len() {
+                assert_eq!(view.scenic_scores[i][j], scenic_score(trees, i, j), "tree {},{}", i, j);
+            }
+        }
+    }
+}
+
+// The original solution, which looks out from every tree separately. It
+// is kept to check ForestView against.
+
+#[cfg(test)]
 fn visibility_map(trees: &[Vec<u32>]) -> Vec<Vec<bool>> {
     let mut visible: Vec<Vec<bool>> = trees
         .iter()
@@ -41,6 +180,7 @@ fn visibility_map(trees: &[Vec<u32>]) -> Vec<Vec<bool>> {
     visible
 }
 
+#[cfg(test)]
 fn mark_trees_visible<I>(indices: I, trees: &[Vec<u32>], visible: &mut [Vec<bool>])
 where
     I: IntoIterator<Item = (usize, usize)>,
@@ -57,22 +197,8 @@ where
     }
 }
 
-fn part2_max_scenic_score(trees: &[Vec<u32>]) -> u32 {
-    trees
-        .iter()
-        .enumerate()
-        .map(|(i, row)| {
-            row.iter()
-                .enumerate()
-                .map(|(j, _)| scenic_score(trees, i, j))
-                .max()
-                .expect("no scenic score for row")
-        })
-        .max()
-        .expect("no scenic score at all")
-}
-
-fn scenic_score(trees: &[Vec<u32>], i: usize, j: usize) -> u32 {
+#[cfg(test)]
+fn scenic_score(trees: &[Vec<u32>], i: usize, j: usize) -> u64 {
     let num_rows = trees.len();
     let num_cols = trees[0].len();
 
@@ -81,9 +207,10 @@ fn scenic_score(trees: &[Vec<u32>], i: usize, j: usize) -> u32 {
     let down = count_visible((i..num_rows).map(|i| (i, j)), trees);
     let up = count_visible((0..=i).rev().map(|i| (i, j)), trees);
 
-    right * left * down * up
+    [right, left, down, up].iter().map(|&count| count as u64).product()
 }
 
+#[cfg(test)]
 fn count_visible<I>(indices: I, trees: &[Vec<u32>]) -> u32
 where
     I: IntoIterator<Item = (usize, usize)>,
@@ -1,11 +1,21 @@
 use itertools::Itertools;
 use std::cmp::Ordering;
-use std::iter::Peekable;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
 
 fn main() {
-    let input = parse_input(INPUT);
+    let input = parse_input(INPUT).unwrap_or_else(|err| panic!("failed to parse input: {}", err));
     println!("part 1: {}", part1(&input));
     println!("part 2: {}", part2(&input));
+
+    // Pass --explain to see where each pair's comparison was decided
+    if std::env::args().any(|arg| arg == "--explain") {
+        for (i, (first, second)) in input.iter().enumerate() {
+            let (ordering, path) = compare_packets(first, second);
+            println!("pair {}: {:?} at {:?}", i + 1, ordering, path);
+        }
+    }
 }
 
 fn part1(pairs: &[(PacketValue, PacketValue)]) -> usize {
@@ -17,11 +27,16 @@ fn part1(pairs: &[(PacketValue, PacketValue)]) -> usize {
         .sum()
 }
 
+#[test]
+fn test_part1() {
+    assert_eq!(part1(&parse_input(_EXAMPLE).unwrap()), 13);
+}
+
 fn packets_in_order(first: &PacketValue, second: &PacketValue) -> bool {
-    match compare_packets(first, second) {
+    match first.cmp(second) {
         Ordering::Less => true,
         Ordering::Greater => false,
-        Ordering::Equal => panic!("found undecided packets! {:?}, {:?}", first, second),
+        Ordering::Equal => panic!("found undecided packets! {}, {}", first, second),
     }
 }
 
@@ -32,108 +47,309 @@ fn part2(pairs: &[(PacketValue, PacketValue)]) -> usize {
     let nested_6 = PacketValue::List(vec![PacketValue::List(vec![PacketValue::Num(6)])]);
     all_packets.push(&nested_6);
 
-    all_packets.sort_unstable_by(|&x, &y| compare_packets(x, y));
+    all_packets.sort_unstable();
 
     let (index_2, _) = all_packets
         .iter()
-        .find_position(|&&x| *x == nested_2)
+        .find_position(|&&x| std::ptr::eq(x, &nested_2))
         .expect("couldn't find nested_2");
 
     let (index_6, _) = all_packets
         .iter()
-        .find_position(|&&x| *x == nested_6)
+        .find_position(|&&x| std::ptr::eq(x, &nested_6))
         .expect("couldn't find nested_6");
 
     (index_2 + 1) * (index_6 + 1)
 }
 
-fn compare_packets(first: &PacketValue, second: &PacketValue) -> Ordering {
+#[test]
+fn test_part2() {
+    assert_eq!(part2(&parse_input(_EXAMPLE).unwrap()), 140);
+}
+
+/// Compares two packets, also returning the path of list indexes to
+/// the point where the comparison was decided. When the packets are
+/// equal the path is empty. Only indexes into lists on both sides are
+/// included, so when a number is compared with a list the path stops at
+/// them.
+fn compare_packets(first: &PacketValue, second: &PacketValue) -> (Ordering, Vec<usize>) {
+    let mut path = Vec::new();
+    let ordering = compare_packets_path(first, second, &mut path);
+    (ordering, path)
+}
+
+#[test]
+fn test_compare_packets() {
+    let parse = |s: &str| s.parse::<PacketValue>().unwrap();
+
+    assert_eq!(compare_packets(&parse("[1,1,3,1,1]"), &parse("[1,1,5,1,1]")), (Ordering::Less, vec![2]));
+    assert_eq!(compare_packets(&parse("[[4,4],4,4]"), &parse("[[4,4],4,4,4]")), (Ordering::Less, vec![]));
+    assert_eq!(
+        compare_packets(&parse("[1,[2,[3,[4,[5,6,7]]]],8,9]"), &parse("[1,[2,[3,[4,[5,6,0]]]],8,9]")),
+        (Ordering::Greater, vec![1, 1, 1, 1, 2]),
+    );
+    assert_eq!(compare_packets(&parse("[[1],[2]]"), &parse("[[1],[2]]")), (Ordering::Equal, vec![]));
+
+    // Mixed lists and numbers
+    assert_eq!(compare_packets(&parse("[[1],[2,3,4]]"), &parse("[[1],4]")), (Ordering::Less, vec![1]));
+    assert_eq!(compare_packets(&parse("[[5,6]]"), &parse("[5]")), (Ordering::Greater, vec![0]));
+    assert_eq!(compare_packets(&parse("[1,[[2],3]]"), &parse("[1,[2,[3]]]")), (Ordering::Equal, vec![]));
+    assert_eq!(compare_packets(&parse("[9]"), &parse("[[8,[7]]]")), (Ordering::Greater, vec![0]));
+}
+
+fn compare_packets_path(first: &PacketValue, second: &PacketValue, path: &mut Vec<usize>) -> Ordering {
     match (first, second) {
         (PacketValue::Num(x), PacketValue::Num(y)) => x.cmp(y),
-        (PacketValue::List(xs), PacketValue::List(ys)) => {
-            for (x, y) in xs.iter().zip(ys) {
-                let ordering = compare_packets(x, y);
-                if ordering != Ordering::Equal {
-                    return ordering;
-                }
-            }
+        (PacketValue::List(xs), PacketValue::List(ys)) => compare_packet_lists(xs, ys, true, path),
+        // Numbers compared with lists are treated as a list of just that
+        // number, which doesn't have its own index in the path
+        (PacketValue::List(xs), y @ PacketValue::Num(_)) => {
+            compare_packet_lists(xs, std::slice::from_ref(y), false, path)
+        }
+        (x @ PacketValue::Num(_), PacketValue::List(ys)) => {
+            compare_packet_lists(std::slice::from_ref(x), ys, false, path)
+        }
+    }
+}
 
-            xs.len().cmp(&ys.len())
+fn compare_packet_lists(xs: &[PacketValue], ys: &[PacketValue], record_indexes: bool, path: &mut Vec<usize>) -> Ordering {
+    for (i, (x, y)) in xs.iter().zip(ys).enumerate() {
+        if record_indexes {
+            path.push(i);
         }
-        (xs @ PacketValue::List(_), y @ PacketValue::Num(_)) => {
-            compare_packets(xs, &PacketValue::List(vec![y.clone()]))
+        let ordering = compare_packets_path(x, y, path);
+        if ordering != Ordering::Equal {
+            return ordering;
         }
-        (x @ PacketValue::Num(_), ys @ PacketValue::List(_)) => {
-            compare_packets(&PacketValue::List(vec![x.clone()]), ys)
+        if record_indexes {
+            path.pop();
         }
     }
+
+    xs.len().cmp(&ys.len())
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Packets are equal when they compare as equal, so a number is equal to
+/// a list of just that number.
+#[derive(Debug, Clone)]
 enum PacketValue {
     List(Vec<PacketValue>),
     Num(u32),
 }
 
-fn parse_input(input: &str) -> Vec<(PacketValue, PacketValue)> {
-    let mut lines = input.lines();
+impl PartialEq for PacketValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PacketValue {}
+
+impl Ord for PacketValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_packets_path(self, other, &mut Vec::new())
+    }
+}
+
+impl PartialOrd for PacketValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for PacketValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PacketValue::Num(x) => write!(f, "{}", x),
+            PacketValue::List(xs) => write!(f, "[{}]", xs.iter().join(",")),
+        }
+    }
+}
+
+impl From<&PacketValue> for serde_json::Value {
+    fn from(packet: &PacketValue) -> Self {
+        match packet {
+            PacketValue::Num(x) => serde_json::Value::from(*x),
+            PacketValue::List(xs) => serde_json::Value::Array(xs.iter().map(serde_json::Value::from).collect()),
+        }
+    }
+}
+
+impl TryFrom<&serde_json::Value> for PacketValue {
+    type Error = String;
+
+    fn try_from(value: &serde_json::Value) -> Result<Self, Self::Error> {
+        match value {
+            serde_json::Value::Array(xs) => Ok(PacketValue::List(
+                xs.iter().map(PacketValue::try_from).collect::<Result<_, _>>()?,
+            )),
+            serde_json::Value::Number(x) => x
+                .as_u64()
+                .and_then(|x| u32::try_from(x).ok())
+                .map(PacketValue::Num)
+                .ok_or_else(|| format!("number {} is not a u32", x)),
+            other => Err(format!("packets can't contain {}", other)),
+        }
+    }
+}
+
+#[test]
+fn test_packet_eq() {
+    let parse = |s: &str| s.parse::<PacketValue>().unwrap();
+    assert_eq!(parse("[1,[2]]"), parse("[[1],2]"));
+    assert_ne!(parse("[1]"), parse("[1,1]"));
+    assert_eq!(PacketValue::Num(1), parse("[[1]]"));
+
+    let mut packets = vec![parse("[1,1]"), parse("[[1]]"), parse("[0]"), parse("[1]")];
+    packets.sort_unstable();
+    packets.dedup();
+    assert_eq!(packets.iter().join(" "), "[0] [[1]] [1,1]");
+}
+
+#[test]
+fn test_packet_json() {
+    for line in _EXAMPLE.lines().filter(|line| !line.is_empty()) {
+        let packet: PacketValue = line.parse().unwrap();
+        assert_eq!(packet.to_string(), line);
+
+        let json = serde_json::Value::from(&packet);
+        assert_eq!(json, serde_json::from_str::<serde_json::Value>(line).unwrap());
+        assert_eq!(PacketValue::try_from(&json).map(|p| p.to_string()), Ok(packet.to_string()));
+    }
+
+    assert!(PacketValue::try_from(&serde_json::json!([1, "a"])).is_err());
+    assert!(PacketValue::try_from(&serde_json::json!([-1])).is_err());
+}
+
+#[derive(Debug, PartialEq)]
+struct ParsePacketError {
+    column: usize,
+    message: String,
+}
+
+impl fmt::Display for ParsePacketError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+fn parse_input(input: &str) -> Result<Vec<(PacketValue, PacketValue)>, String> {
+    let mut lines = input.lines().enumerate();
     let mut pairs = vec![];
     loop {
-        let lhs = parse_packet(lines.next().expect("no line for lhs"));
-        let rhs = parse_packet(lines.next().expect("no line for rhs"));
+        let lhs = parse_packet_line(lines.next(), "lhs")?;
+        let rhs = parse_packet_line(lines.next(), "rhs")?;
         pairs.push((lhs, rhs));
 
         match lines.next() {
             None => break,
-            Some("") => continue,
-            Some(l) => panic!("expected empty line or end, got: \"{}\"", l),
+            Some((_, "")) => continue,
+            Some((line_num, l)) => {
+                return Err(format!("line {}: expected empty line or end, got: \"{}\"", line_num + 1, l))
+            }
         }
     }
-    pairs
+    Ok(pairs)
 }
 
-fn parse_packet(input: &str) -> PacketValue {
-    let mut chars = input.chars().peekable();
-    assert_eq!(
-        chars.next(),
-        Some('['),
-        "expected top level packet to be list"
-    );
+fn parse_packet_line(line: Option<(usize, &str)>, name: &str) -> Result<PacketValue, String> {
+    let (line_num, line) = line.ok_or_else(|| format!("no line for {}", name))?;
+    line.parse().map_err(|err| format!("line {}, {}", line_num + 1, err))
+}
+
+impl FromStr for PacketValue {
+    type Err = ParsePacketError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut parser = PacketParser { bytes: input.as_bytes(), pos: 0 };
+
+        if parser.peek() != Some(b'[') {
+            return Err(parser.error("expected top level packet to be list"));
+        }
+        let val = parser.parse_value()?;
+
+        if parser.pos != parser.bytes.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
 
-    let val = PacketValue::List(parse_packet_list(&mut chars));
+        Ok(val)
+    }
+}
 
-    assert_eq!(chars.next(), None);
+#[test]
+fn test_parse_packet_errors() {
+    let err = |s: &str| s.parse::<PacketValue>().unwrap_err();
 
-    val
+    assert_eq!(err("1").column, 1);
+    assert_eq!(err("[1,2").column, 5);
+    assert_eq!(err("[1,,2]").column, 4);
+    assert_eq!(err("[1,2]]").column, 6);
+    assert_eq!(err("[1,x]").column, 4);
+    assert_eq!(err("[99999999999]").column, 2);
 }
 
-fn parse_packet_list<I>(chars: &mut Peekable<I>) -> Vec<PacketValue>
-where
-    I: Iterator<Item = char>,
-{
-    let mut elems: Vec<PacketValue> = vec![];
-
-    while let Some(c) = chars.next() {
-        match c {
-            '[' => elems.push(PacketValue::List(parse_packet_list(chars))),
-            ',' => continue,
-            ']' => return elems,
-            c => {
-                let mut num: u32 = c.to_digit(10).expect("couldn't convert digit");
-                while let Some(c) = chars.peek() {
-                    if !c.is_ascii_digit() {
-                        break;
-                    }
-                    let c = chars.next().unwrap();
-                    num *= 10;
-                    num += c.to_digit(10).expect("couldn't convert digit");
+struct PacketParser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PacketParser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn error(&self, message: &str) -> ParsePacketError {
+        // Columns are 1-based so they line up with editors
+        ParsePacketError { column: self.pos + 1, message: message.to_string() }
+    }
+
+    fn parse_value(&mut self) -> Result<PacketValue, ParsePacketError> {
+        match self.peek() {
+            Some(b'[') => {
+                self.pos += 1;
+                self.parse_list_rest()
+            }
+            Some(c) if c.is_ascii_digit() => self.parse_num(),
+            Some(_) => Err(self.error("expected list or number")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    // Parses the elements of a list after the opening bracket
+    fn parse_list_rest(&mut self) -> Result<PacketValue, ParsePacketError> {
+        let mut elems = vec![];
+
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(PacketValue::List(elems));
+        }
+
+        loop {
+            elems.push(self.parse_value()?);
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(PacketValue::List(elems));
                 }
-                elems.push(PacketValue::Num(num));
+                Some(_) => return Err(self.error("expected ',' or ']'")),
+                None => return Err(self.error("unexpected end of input, expected ']'")),
             }
         }
     }
 
-    panic!("malformed list")
+    fn parse_num(&mut self) -> Result<PacketValue, ParsePacketError> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+
+        let digits = std::str::from_utf8(&self.bytes[start..self.pos]).expect("digits are ASCII");
+        digits.parse().map(PacketValue::Num).map_err(|_| ParsePacketError {
+            column: start + 1,
+            message: format!("number {} is too large", digits),
+        })
+    }
 }
 
 const _EXAMPLE: &str = "[1,1,3,1,1]
@@ -9,91 +9,168 @@ use nom::{
 };
 
 fn main() {
-    // let readings = parse_input(_EXAMPLE);
-    // let row = 10;
-    // let part2_max = 20;
-
-    let readings = parse_input(INPUT);
-    let row = 2000000;
-    let part2_max = 4000000;
+    // Pass --example to use the example's row and search bound, or pass
+    // a row and search bound to override them.
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let (input, mut row, mut search_max) = match args.iter().position(|arg| arg == "--example") {
+        Some(i) => {
+            args.remove(i);
+            (_EXAMPLE, 10, 20)
+        }
+        None => (INPUT, INPUT_ROW, INPUT_SEARCH_MAX),
+    };
+    if let Some(arg) = args.first() {
+        row = arg.parse().expect("parse row");
+    }
+    if let Some(arg) = args.get(1) {
+        search_max = arg.parse().expect("parse search bound");
+    }
 
+    let readings = parse_input(input);
     println!("part 1: {}", part1(&readings, row));
-    println!("part 2: {}", part2(&readings, part2_max));
+    println!("part 2: {}", part2(&readings, search_max));
+}
+
+const INPUT_ROW: i64 = 2000000;
+const INPUT_SEARCH_MAX: i64 = 4000000;
+
+fn part1(readings: &[SensorReading], row: i64) -> i64 {
+    let covered = merge_intervals(
+        readings
+            .iter()
+            .filter_map(|reading| {
+                let reach = reading.radius() - (reading.sensor.1 - row).abs();
+                (reach >= 0).then_some((reading.sensor.0 - reach, reading.sensor.0 + reach))
+            })
+            .collect(),
+    );
+
+    // Known beacons can't be counted as places a beacon can't be
+    let beacons_in_row = readings
+        .iter()
+        .map(|reading| reading.beacon)
+        .filter(|&(x, y)| y == row && covered.iter().any(|&(lo, hi)| x >= lo && x <= hi))
+        .collect::<HashSet<Point>>();
+
+    covered.iter().map(|(lo, hi)| hi - lo + 1).sum::<i64>() - beacons_in_row.len() as i64
+}
+
+#[test]
+fn test_part1() {
+    assert_eq!(part1(&parse_input(_EXAMPLE), 10), 26);
 }
 
-fn part1(readings: &[SensorReading], row: i32) -> usize {
-    // Find max bounds
-    let first_reading = readings.iter().next().expect("empty covering set");
-    let mut min_x = first_reading.beacon.0;
-    let mut max_x = first_reading.beacon.1;
-    for reading in readings.iter() {
-        min_x = std::cmp::min(min_x, reading.sensor.0);
-        max_x = std::cmp::max(max_x, reading.sensor.1);
+/// Merges inclusive intervals into sorted, non-overlapping ones.
+fn merge_intervals(mut intervals: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
+    intervals.sort_unstable();
+    let mut merged: Vec<(i64, i64)> = Vec::with_capacity(intervals.len());
+    for (lo, hi) in intervals {
+        match merged.last_mut() {
+            // Adjacent intervals merge too since these are whole numbers
+            Some(last) if lo <= last.1 + 1 => last.1 = last.1.max(hi),
+            _ => merged.push((lo, hi)),
+        }
     }
+    merged
+}
+
+#[test]
+fn test_merge_intervals() {
+    assert_eq!(
+        merge_intervals(vec![(5, 8), (0, 2), (3, 3), (7, 12), (14, 14)]),
+        vec![(0, 3), (5, 12), (14, 14)]
+    );
+}
 
-    let max_distance = manhattan_distance(first_reading.beacon, first_reading.sensor);
-    min_x -= max_distance;
-    max_x += max_distance;
+/// Finds the first number between lo and hi that isn't in any interval.
+fn first_gap(lo: i64, hi: i64, intervals: Vec<(i64, i64)>) -> Option<i64> {
+    let mut candidate = lo;
+    for (start, end) in merge_intervals(intervals) {
+        if start > candidate {
+            break;
+        }
+        candidate = candidate.max(end + 1);
+    }
+    (candidate <= hi).then_some(candidate)
+}
 
-    // Iterate over the row in question
-    (min_x..=max_x)
-        .filter(|&x| is_point_impossible(readings, x, row))
-        .count()
+fn part2(readings: &[SensorReading], search_max: i64) -> i64 {
+    let (x, y) = find_distress_beacon(readings, search_max).expect("no solution found");
+    x * 4000000 + y
 }
 
-fn is_point_impossible(readings: &[SensorReading], x: i32, y: i32) -> bool {
-    readings
-        .iter()
-        .any(|reading| (x, y) != reading.beacon && in_sensor_range(reading, x, y))
+#[test]
+fn test_part2() {
+    assert_eq!(part2(&parse_input(_EXAMPLE), 20), 56000011);
 }
 
-fn in_sensor_range(reading: &SensorReading, x: i32, y: i32) -> bool {
-    let sensor_distance = manhattan_distance(reading.sensor, reading.beacon);
-    let point_distance = manhattan_distance(reading.sensor, (x, y));
+/// The distress beacon is the only point in the search area not covered by
+/// a sensor, so it has covered points on each side. That puts it just
+/// outside some sensor's range, on one of the four diagonal lines around
+/// that sensor. This is usually where two sensors' ranges are one apart,
+/// but checking every sensor's lines also catches points on the edge of
+/// the search area. Along each line, the sensor ranges are intervals.
+fn find_distress_beacon(readings: &[SensorReading], search_max: i64) -> Option<Point> {
+    for reading in readings {
+        let (x, y) = reading.sensor;
+        let distance = reading.radius() + 1;
+        for offset in [-distance, distance] {
+            // x + y is constant along these lines
+            let sum = x + y + offset;
+            let covered = readings
+                .iter()
+                .filter_map(|other| diagonal_coverage(other, sum, 1))
+                .collect();
+            let lo = (sum - search_max).max(0);
+            if let Some(px) = first_gap(lo, sum.min(search_max), covered) {
+                return Some((px, sum - px));
+            }
 
-    point_distance <= sensor_distance
+            // x - y is constant along these lines
+            let difference = x - y + offset;
+            let covered = readings
+                .iter()
+                .filter_map(|other| diagonal_coverage(other, difference, -1))
+                .collect();
+            let hi = (difference + search_max).min(search_max);
+            if let Some(px) = first_gap(difference.max(0), hi, covered) {
+                return Some((px, px - difference));
+            }
+        }
+    }
+    None
 }
 
-fn part2(readings: &[SensorReading], max_bound: i32) -> i64 {
-    let known_beacons = readings
-        .iter()
-        .map(|reading| reading.beacon)
-        .collect::<HashSet<Point>>();
+/// Returns the range of x a sensor covers on the diagonal line where
+/// x + y == constant (slope 1) or x - y == constant (slope -1).
+fn diagonal_coverage(reading: &SensorReading, constant: i64, slope: i64) -> Option<(i64, i64)> {
+    let (sx, sy) = reading.sensor;
+    let radius = reading.radius();
 
-    // Since there is only a single point for the solution, it must lie just
-    // outside the boundary of a scanner.
-    let (x, y) = readings
-        .iter()
-        .flat_map(reading_outside_boundary)
-        .filter(|&(x, y)| x >= 0 && x <= max_bound && y >= 0 && y <= max_bound)
-        .filter(|point| !known_beacons.contains(point))
-        .find(|&(x, y)| readings.iter().all(|reading| !in_sensor_range(reading, x, y)))
-        .expect("no solution found");
-
-    (x as i64) * 4000000 + (y as i64)
-}
-
-fn reading_outside_boundary(reading: &SensorReading) -> Vec<Point> {
-    let distance = manhattan_distance(reading.sensor, reading.beacon) + 1;
-    (0..distance)
-        .flat_map(|i| {
-            vec![
-                // Start vertical, go down and to the right
-                (reading.sensor.0 + i, reading.sensor.1 + distance - i),
-                // Start right, go down and to the left
-                (reading.sensor.0 + distance - i, reading.sensor.1 - i),
-                // Start down, go up and to the left
-                (reading.sensor.0 - i, reading.sensor.1 - distance + i),
-                // Start left, go up and to the right
-                (reading.sensor.0 - distance + i, reading.sensor.1 + i),
-            ]
-        })
-        .collect()
+    // Rotating by 45 degrees turns a sensor's range into a square, so a
+    // diagonal line crosses it if it is close enough in one coordinate.
+    // The other coordinate along the line is 2x - constant.
+    let (across, along) = if slope == 1 { (sx + sy, sx - sy) } else { (sx - sy, sx + sy) };
+    if (constant - across).abs() > radius {
+        return None;
+    }
+    let lo = (along - radius + constant + 1).div_euclid(2);
+    let hi = (along + radius + constant).div_euclid(2);
+    (lo <= hi).then_some((lo, hi))
+}
+
+#[test]
+fn test_diagonal_coverage() {
+    let reading = SensorReading { sensor: (0, 0), beacon: (2, 0) };
+    assert_eq!(diagonal_coverage(&reading, 0, 1), Some((-1, 1)));
+    assert_eq!(diagonal_coverage(&reading, 2, 1), Some((0, 2)));
+    assert_eq!(diagonal_coverage(&reading, -1, -1), Some((-1, 0)));
+    assert_eq!(diagonal_coverage(&reading, 3, -1), None);
 }
 
-type Point = (i32, i32);
+type Point = (i64, i64);
 
-fn manhattan_distance(a: Point, b: Point) -> i32 {
+fn manhattan_distance(a: Point, b: Point) -> i64 {
     (a.0 - b.0).abs() + (a.1 - b.1).abs()
 }
 
@@ -103,6 +180,12 @@ struct SensorReading {
     beacon: Point,
 }
 
+impl SensorReading {
+    fn radius(&self) -> i64 {
+        manhattan_distance(self.sensor, self.beacon)
+    }
+}
+
 fn parse_input(input: &str) -> Vec<SensorReading> {
     input
         .lines()
@@ -116,13 +199,13 @@ fn parse_input(input: &str) -> Vec<SensorReading> {
 
 fn parse_input_line(input: &str) -> IResult<&str, SensorReading> {
     let (input, _) = tag("Sensor at x=")(input)?;
-    let (input, sensor_x) = parse_i32(input)?;
+    let (input, sensor_x) = parse_i64(input)?;
     let (input, _) = tag(", y=")(input)?;
-    let (input, sensor_y) = parse_i32(input)?;
+    let (input, sensor_y) = parse_i64(input)?;
     let (input, _) = tag(": closest beacon is at x=")(input)?;
-    let (input, beacon_x) = parse_i32(input)?;
+    let (input, beacon_x) = parse_i64(input)?;
     let (input, _) = tag(", y=")(input)?;
-    let (input, beacon_y) = parse_i32(input)?;
+    let (input, beacon_y) = parse_i64(input)?;
     Ok((
         input,
         SensorReading {
@@ -132,7 +215,7 @@ fn parse_input_line(input: &str) -> IResult<&str, SensorReading> {
     ))
 }
 
-fn parse_i32(input: &str) -> IResult<&str, i32> {
+fn parse_i64(input: &str) -> IResult<&str, i64> {
     let (i, number) = map_res(recognize(preceded(opt(tag("-")), digit1)), |s: &str| {
         s.parse()
     })(input)?;
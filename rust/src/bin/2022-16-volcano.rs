@@ -1,6 +1,6 @@
 use std::cmp::Reverse;
 use std::collections::BinaryHeap;
-use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::collections::HashMap;
 
 use nom::{
     bytes::complete::tag,
@@ -12,87 +12,231 @@ use nom::{
 
 fn main() {
     let tunnels = parse_input(INPUT);
-    let turns = 30;
-    println!("part 1: {}", max_pressure_release(&tunnels, turns));
+
+    let part1 = max_pressure_release(&tunnels, 30, 1);
+    println!("part 1: {}", part1.total_pressure);
+
+    // In part 2 we spend 4 minutes teaching an elephant to help us
+    let part2 = max_pressure_release(&tunnels, 26, 2);
+    println!("part 2: {}", part2.total_pressure);
+    print!("{}", part2);
 }
 
 type TotalPressure = u32;
 
-fn max_pressure_release(tunnels: &[Tunnel], turns: u32) -> TotalPressure {
-    let tunnels_by_name: HashMap<&String, &Tunnel> = tunnels
-        .iter()
-        .map(|tunnel| (&tunnel.name, tunnel))
-        .collect();
-
-    let distances = tunnel_distances(tunnels);
-    let tunnels_with_pressure: Vec<String> = tunnels
-        .iter()
-        .filter(|tunnel| tunnel.flow_rate > 0)
-        .map(|tunnel| tunnel.name.clone())
-        .collect();
+/// The tables in `best_pressure_per_subset` and `max_pressure_release`
+/// have an entry for every subset of valves, and splitting valves
+/// between agents walks 3^n pairs of subsets, which is billions already
+/// at this many. The puzzle input has 15.
+const MAX_VALVES: usize = 20;
 
-    let mut paths: HashMap<Path, TotalPressure> = HashMap::new();
-    let mut queue: VecDeque<(Path, TotalPressure)> = VecDeque::new();
-    queue.push_back((
-        Path {
-            tunnel: "AA".to_string(),
-            remaining_turns: turns,
-            opened_valves: BTreeSet::new(),
-        },
-        0,
-    ));
+/// Valves with a positive flow rate, indexed compactly so sets of
+/// opened valves fit in a bitmask.
+struct ValveGraph {
+    names: Vec<String>,
+    flow_rates: Vec<u32>,
+    /// distances[i][j] is the number of steps from valve i to valve j
+    distances: Vec<Vec<Steps>>,
+    /// Number of steps from the starting tunnel to each valve
+    start_distances: Vec<Steps>,
+}
 
-    let mut max_pressure = 0;
+impl ValveGraph {
+    fn new(tunnels: &[Tunnel], start: &str) -> Self {
+        let valves: Vec<&Tunnel> = tunnels.iter().filter(|tunnel| tunnel.flow_rate > 0).collect();
+        assert!(
+            valves.len() <= MAX_VALVES,
+            "{} valves with flow is too many for tables over every subset of valves",
+            valves.len()
+        );
 
-    while let Some((path, total_pressure)) = queue.pop_front() {
-        if let Some(&existing_pressure) = paths.get(&path) {
-            if existing_pressure >= total_pressure {
-                // We already have a better path here
-                continue;
+        let tunnel_distances = tunnel_distances(tunnels);
+        let distance = |source: &String, dest: &String| -> Steps {
+            if source == dest {
+                return 0;
             }
+            *tunnel_distances.get(&(source, dest)).expect("no distance found")
+        };
+
+        let start = start.to_string();
+        ValveGraph {
+            names: valves.iter().map(|valve| valve.name.clone()).collect(),
+            flow_rates: valves.iter().map(|valve| valve.flow_rate).collect(),
+            distances: valves
+                .iter()
+                .map(|source| valves.iter().map(|dest| distance(&source.name, &dest.name)).collect())
+                .collect(),
+            start_distances: valves.iter().map(|dest| distance(&start, &dest.name)).collect(),
         }
-        paths.insert(path.clone(), total_pressure);
+    }
 
-        max_pressure = std::cmp::max(max_pressure, total_pressure);
+    fn num_valves(&self) -> usize {
+        self.names.len()
+    }
+}
 
-        for next in tunnels_with_pressure.iter() {
-            if path.opened_valves.contains(next) {
-                continue;
-            }
+/// A valve and the minute during which it was opened.
+type ValveOpening = (usize, u32);
 
-            if let Some(&dist) = distances.get(&(&path.tunnel, next)) {
-                let remaining_turns = match path.remaining_turns.checked_sub(dist + 1) {
-                    Some(x) => x,
-                    None => continue,
-                };
+/// The best pressure release when one agent opens exactly a given set
+/// of valves, along with the order it opened them in.
+#[derive(Debug, Clone)]
+struct SubsetBest {
+    total_pressure: TotalPressure,
+    openings: Vec<ValveOpening>,
+}
 
-                let mut opened_valves = path.opened_valves.clone();
-                opened_valves.insert(next.clone());
+/// Computes, for every set of valves, the best pressure a single agent
+/// can release by opening exactly those valves. Sets the agent can't
+/// open in time are `None`.
+fn best_pressure_per_subset(graph: &ValveGraph, turns: u32) -> Vec<Option<SubsetBest>> {
+    let mut best = vec![None; 1 << graph.num_valves()];
+    let mut openings = Vec::new();
+    explore_openings(graph, turns, None, turns, 0, 0, &mut openings, &mut best);
+    best
+}
 
-                let next_tunnel = tunnels_by_name.get(&next).expect("couldn't find next");
-                let next_pressure = next_tunnel.flow_rate * remaining_turns;
+#[allow(clippy::too_many_arguments)]
+fn explore_openings(
+    graph: &ValveGraph,
+    turns: u32,
+    current: Option<usize>,
+    remaining_turns: u32,
+    opened: usize,
+    total_pressure: TotalPressure,
+    openings: &mut Vec<ValveOpening>,
+    best: &mut Vec<Option<SubsetBest>>,
+) {
+    if best[opened].as_ref().is_none_or(|existing| existing.total_pressure < total_pressure) {
+        best[opened] = Some(SubsetBest { total_pressure, openings: openings.clone() });
+    }
 
-                let new_path = Path {
-                    tunnel: next.clone(),
-                    remaining_turns,
-                    opened_valves,
-                };
+    for next in 0..graph.num_valves() {
+        if opened & (1 << next) != 0 {
+            continue;
+        }
+
+        let dist = match current {
+            Some(current) => graph.distances[current][next],
+            None => graph.start_distances[next],
+        };
+
+        // It takes a minute to open the valve once we get there
+        let remaining_turns = match remaining_turns.checked_sub(dist + 1) {
+            Some(x) if x > 0 => x,
+            _ => continue,
+        };
+
+        openings.push((next, turns - remaining_turns));
+        explore_openings(
+            graph,
+            turns,
+            Some(next),
+            remaining_turns,
+            opened | (1 << next),
+            total_pressure + graph.flow_rates[next] * remaining_turns,
+            openings,
+            best,
+        );
+        openings.pop();
+    }
+}
+
+#[derive(Debug)]
+struct Plan {
+    total_pressure: TotalPressure,
+    /// The valves each agent opens, with the minute they opened it
+    schedules: Vec<Vec<(String, u32)>>,
+}
+
+impl std::fmt::Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, schedule) in self.schedules.iter().enumerate() {
+            let openings: Vec<String> = schedule
+                .iter()
+                .map(|(valve, minute)| format!("{}@{}", valve, minute))
+                .collect();
+            writeln!(f, "agent {}: {}", i + 1, openings.join(", "))?;
+        }
+        Ok(())
+    }
+}
 
-                // println!("{:?} {}", new_path, total_pressure + next_pressure);
+/// Finds the best plan for `agents` agents that all start at AA and
+/// move simultaneously. Every agent's best plan for each subset of
+/// valves is the same, so we only need to split the valves into
+/// disjoint subsets, one per agent.
+fn max_pressure_release(tunnels: &[Tunnel], turns: u32, agents: usize) -> Plan {
+    assert!(agents > 0, "need at least one agent");
 
-                queue.push_back((new_path, total_pressure + next_pressure));
+    let graph = ValveGraph::new(tunnels, "AA");
+    let best = best_pressure_per_subset(&graph, turns);
+    let num_masks = best.len();
+
+    // combined[k][mask] is the best pressure k + 1 agents can release
+    // using only the valves in mask, along with the subset of valves
+    // the last agent opens.
+    let mut combined: Vec<Vec<(TotalPressure, usize)>> = Vec::new();
+    for k in 0..agents {
+        let mut level = vec![(0, 0); num_masks];
+        for (mask, entry) in level.iter_mut().enumerate() {
+            // Enumerate every subset of mask, including mask itself
+            let mut subset = mask;
+            loop {
+                if let Some(subset_best) = &best[subset] {
+                    let rest = match k {
+                        0 => 0,
+                        _ => combined[k - 1][mask ^ subset].0,
+                    };
+                    let total = subset_best.total_pressure + rest;
+                    if total > entry.0 {
+                        *entry = (total, subset);
+                    }
+                }
+
+                if subset == 0 {
+                    break;
+                }
+                subset = (subset - 1) & mask;
             }
         }
+        combined.push(level);
     }
 
-    max_pressure
+    // Walk back through the levels to find each agent's valves
+    let mut mask = num_masks - 1;
+    let total_pressure = combined[agents - 1][mask].0;
+    let mut schedules = Vec::new();
+    for level in combined.iter().rev() {
+        let subset = level[mask].1;
+        let openings = best[subset].as_ref().map_or(&[][..], |subset_best| &subset_best.openings);
+        schedules.push(
+            openings
+                .iter()
+                .map(|&(valve, minute)| (graph.names[valve].clone(), minute))
+                .collect(),
+        );
+        mask ^= subset;
+    }
+
+    Plan { total_pressure, schedules }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
-struct Path {
-    tunnel: String,
-    remaining_turns: u32,
-    opened_valves: BTreeSet<String>,
+#[test]
+fn test_max_pressure_release() {
+    let tunnels = parse_input(_EXAMPLE);
+    assert_eq!(max_pressure_release(&tunnels, 30, 1).total_pressure, 1651);
+
+    let plan = max_pressure_release(&tunnels, 26, 2);
+    assert_eq!(plan.total_pressure, 1707);
+
+    // The schedules should account for all of the pressure and not
+    // open any valve twice
+    let flow_rates: HashMap<&String, u32> = tunnels.iter().map(|tunnel| (&tunnel.name, tunnel.flow_rate)).collect();
+    let opened: Vec<&(String, u32)> = plan.schedules.iter().flatten().collect();
+    let total: u32 = opened.iter().map(|(valve, minute)| flow_rates[valve] * (26 - minute)).sum();
+    assert_eq!(total, 1707);
+    assert_eq!(opened.len(), opened.iter().map(|(valve, _)| valve).collect::<std::collections::HashSet<_>>().len());
 }
 
 type Steps = u32;
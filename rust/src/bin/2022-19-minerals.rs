@@ -1,17 +1,50 @@
-use std::collections::{HashSet, VecDeque};
-
 use nom::{bytes::complete::tag, character::complete::digit1, combinator::map_res, IResult};
 
 fn main() {
-    let input = parse_blueprints(_EXAMPLE);
-    println!("{:?}", input);
+    let blueprints = parse_blueprints(INPUT);
+    println!("part 1: {}", part1_quality_level_sum(&blueprints));
+    println!("part 2: {}", part2_geode_product(&blueprints));
+}
+
+fn part1_quality_level_sum(blueprints: &[Blueprint]) -> u32 {
+    blueprints
+        .iter()
+        .zip(maximize_geodes_parallel(blueprints, 24))
+        .map(|(blueprint, geodes)| blueprint.id * geodes)
+        .sum()
+}
+
+fn part2_geode_product(blueprints: &[Blueprint]) -> u32 {
+    let first_three = &blueprints[..blueprints.len().min(3)];
+    maximize_geodes_parallel(first_three, 32).iter().product()
+}
 
-    println!("{}", maximize_geodes(&input[0], 21));
+#[test]
+fn test_example() {
+    let blueprints = parse_blueprints(_EXAMPLE);
+    assert_eq!(maximize_geodes_parallel(&blueprints, 24), vec![9, 12]);
+    assert_eq!(part1_quality_level_sum(&blueprints), 33);
+    assert_eq!(maximize_geodes_parallel(&blueprints, 32), vec![56, 62]);
+}
+
+/// Solves each blueprint on its own thread
+fn maximize_geodes_parallel(blueprints: &[Blueprint], minutes: Minutes) -> Vec<Geodes> {
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = blueprints
+            .iter()
+            .map(|blueprint| scope.spawn(move || maximize_geodes(blueprint, minutes)))
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("blueprint thread panicked"))
+            .collect()
+    })
 }
 
 type Minutes = u32;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone)]
 struct SimulationState {
     remaining_minutes: Minutes,
     ore: Ore,
@@ -24,32 +57,42 @@ struct SimulationState {
     geode_robots: u32,
 }
 
-struct SimulationQueue {
-    seen_states: HashSet<SimulationState>,
-    queue: VecDeque<SimulationState>,
+#[derive(Debug, Clone, Copy)]
+enum Robot {
+    Ore,
+    Clay,
+    Obsidian,
+    Geode,
 }
 
-impl SimulationQueue {
-    fn new() -> Self {
-        Self {
-            seen_states: HashSet::new(),
-            queue: VecDeque::new(),
-        }
-    }
+/// Max out robots based on max resource dependencies. There is no point
+/// going over this number because we can only spend that much of a
+/// resource per minute, so we would just generate a surplus each turn.
+struct RobotCaps {
+    ore_robots: u32,
+    clay_robots: u32,
+    obsidian_robots: u32,
+}
 
-    fn insert_state(&mut self, state: SimulationState) {
-        if !self.seen_states.contains(&state) {
-            self.queue.push_back(state.clone());
-            self.seen_states.insert(state);
+impl RobotCaps {
+    fn new(blueprint: &Blueprint) -> Self {
+        RobotCaps {
+            ore_robots: *[
+                blueprint.ore_robot_cost_ore,
+                blueprint.clay_robot_cost_ore,
+                blueprint.obsidian_robot_cost_ore,
+                blueprint.geode_robot_cost_ore,
+            ]
+            .iter()
+            .max()
+            .expect("no max"),
+            clay_robots: blueprint.obsidian_robot_cost_clay,
+            obsidian_robots: blueprint.geode_robot_cost_obsidian,
         }
     }
 }
 
-fn maximize_geodes(blueprint: &Blueprint, minutes: u32) -> Geodes {
-    let mut max_geodes = 0;
-
-    let mut queue = SimulationQueue::new();
-
+fn maximize_geodes(blueprint: &Blueprint, minutes: Minutes) -> Geodes {
     let initial_state = SimulationState {
         remaining_minutes: minutes,
         ore: 0,
@@ -61,83 +104,99 @@ fn maximize_geodes(blueprint: &Blueprint, minutes: u32) -> Geodes {
         geodes: 0,
         geode_robots: 0,
     };
-    queue.insert_state(initial_state);
-
-    // Max out robots based on max resource dependencies. There is no point
-    // going over this number because we will just generate a surplus each turn.
-    let max_ore_robots: u32 = *[
-        blueprint.ore_robot_cost_ore,
-        blueprint.clay_robot_cost_ore,
-        blueprint.obsidian_robot_cost_ore,
-        blueprint.geode_robot_cost_ore,
-    ]
-    .iter()
-    .max()
-    .expect("no max");
-    let max_clay_robots = blueprint.obsidian_robot_cost_clay;
-    let max_obsidian_robots = blueprint.geode_robot_cost_obsidian;
-
-    while let Some(state) = queue.queue.pop_front() {
-        let remaining_minutes = match state.remaining_minutes.checked_sub(1) {
-            Some(m) => m,
-            None => {
-                max_geodes = std::cmp::max(max_geodes, state.geodes);
-                continue;
-            }
-        };
-
-        let new_state = SimulationState {
-            remaining_minutes,
-            ore: state.ore + state.ore_robots,
-            ore_robots: state.ore_robots,
-            clay: state.clay + state.clay_robots,
-            clay_robots: state.clay_robots,
-            obsidian: state.obsidian + state.obsidian_robots,
-            obsidian_robots: state.obsidian_robots,
-            geodes: state.geodes + state.geode_robots,
-            geode_robots: state.geode_robots,
-        };
-
-
-        if state.ore >= blueprint.ore_robot_cost_ore && state.ore_robots < max_ore_robots {
-            let mut state = state.clone();
-            state.ore -= blueprint.ore_robot_cost_ore;
-            state.ore_robots += 1;
-            queue.insert_state(state);
-        }
 
-        if state.ore >= blueprint.clay_robot_cost_ore && state.clay_robots < max_clay_robots {
-            let mut state = state.clone();
-            state.ore -= blueprint.clay_robot_cost_ore;
-            state.clay_robots += 1;
-            queue.insert_state(state);
-        }
+    let mut max_geodes = 0;
+    search_geodes(blueprint, &RobotCaps::new(blueprint), &initial_state, &mut max_geodes);
+    max_geodes
+}
+
+// Depth first search where each step decides which robot to build
+// next, skipping straight to the minute it is built. That way we never
+// branch on minutes where we just wait.
+fn search_geodes(blueprint: &Blueprint, caps: &RobotCaps, state: &SimulationState, max_geodes: &mut Geodes) {
+    // If we don't build anything else, our geode robots keep cracking
+    let idle_geodes = state.geodes + state.geode_robots * state.remaining_minutes;
+    *max_geodes = std::cmp::max(*max_geodes, idle_geodes);
 
-        if state.ore >= blueprint.obsidian_robot_cost_ore
-            && state.clay >= blueprint.obsidian_robot_cost_clay
-            && state.obsidian_robots < max_obsidian_robots
-        {
-            let mut state = state.clone();
-            state.ore -= blueprint.obsidian_robot_cost_ore;
-            state.clay -= blueprint.obsidian_robot_cost_clay;
-            state.obsidian_robots += 1;
-            queue.insert_state(state);
+    // Even if we could build a geode robot every remaining minute, we
+    // couldn't beat the best we've already found.
+    let t = state.remaining_minutes;
+    let upper_bound = idle_geodes + t * t.saturating_sub(1) / 2;
+    if upper_bound <= *max_geodes {
+        return;
+    }
+
+    // Try geode robots first so we find good solutions early and prune
+    // more
+    for robot in [Robot::Geode, Robot::Obsidian, Robot::Clay, Robot::Ore] {
+        if let Some(next_state) = build_next_robot(blueprint, caps, state, robot) {
+            search_geodes(blueprint, caps, &next_state, max_geodes);
         }
+    }
+}
 
-        if state.ore >= blueprint.geode_robot_cost_ore
-            && state.obsidian >= blueprint.geode_robot_cost_obsidian
-        {
-            let mut state = state.clone();
-            state.ore -= blueprint.geode_robot_cost_ore;
-            state.obsidian -= blueprint.geode_robot_cost_obsidian;
-            state.geode_robots += 1;
-            queue.insert_state(state);
+/// Waits until we can afford the given robot and builds it. Returns
+/// `None` if we would never be able to, if it wouldn't be done in time
+/// to be useful, or if we already have enough of that robot.
+fn build_next_robot(
+    blueprint: &Blueprint,
+    caps: &RobotCaps,
+    state: &SimulationState,
+    robot: Robot,
+) -> Option<SimulationState> {
+    let (cost_ore, cost_clay, cost_obsidian) = match robot {
+        Robot::Ore if state.ore_robots < caps.ore_robots => (blueprint.ore_robot_cost_ore, 0, 0),
+        Robot::Clay if state.clay_robots < caps.clay_robots => (blueprint.clay_robot_cost_ore, 0, 0),
+        Robot::Obsidian if state.obsidian_robots < caps.obsidian_robots => {
+            (blueprint.obsidian_robot_cost_ore, blueprint.obsidian_robot_cost_clay, 0)
         }
+        Robot::Geode => (blueprint.geode_robot_cost_ore, 0, blueprint.geode_robot_cost_obsidian),
+        _ => return None,
+    };
 
-        queue.insert_state(new_state);
+    let wait = *[
+        minutes_until(cost_ore, state.ore, state.ore_robots)?,
+        minutes_until(cost_clay, state.clay, state.clay_robots)?,
+        minutes_until(cost_obsidian, state.obsidian, state.obsidian_robots)?,
+    ]
+    .iter()
+    .max()
+    .expect("no max");
+
+    // Building takes one more minute, and the robot needs at least one
+    // minute afterward to collect anything
+    let elapsed = wait + 1;
+    if elapsed >= state.remaining_minutes {
+        return None;
     }
 
-    max_geodes
+    let mut next = SimulationState {
+        remaining_minutes: state.remaining_minutes - elapsed,
+        ore: state.ore + state.ore_robots * elapsed - cost_ore,
+        clay: state.clay + state.clay_robots * elapsed - cost_clay,
+        obsidian: state.obsidian + state.obsidian_robots * elapsed - cost_obsidian,
+        geodes: state.geodes + state.geode_robots * elapsed,
+        ..state.clone()
+    };
+    match robot {
+        Robot::Ore => next.ore_robots += 1,
+        Robot::Clay => next.clay_robots += 1,
+        Robot::Obsidian => next.obsidian_robots += 1,
+        Robot::Geode => next.geode_robots += 1,
+    }
+    Some(next)
+}
+
+/// Minutes of collecting before we have `cost` of a resource, or `None`
+/// if we have no robots collecting it.
+fn minutes_until(cost: u32, have: u32, robots: u32) -> Option<Minutes> {
+    if have >= cost {
+        Some(0)
+    } else if robots == 0 {
+        None
+    } else {
+        Some((cost - have).div_ceil(robots))
+    }
 }
 
 type Ore = u32;
@@ -147,6 +206,7 @@ type Geodes = u32;
 
 #[derive(Debug)]
 struct Blueprint {
+    id: u32,
     ore_robot_cost_ore: Ore,
     clay_robot_cost_ore: Ore,
     obsidian_robot_cost_ore: Ore,
@@ -168,7 +228,7 @@ fn parse_blueprints(input: &str) -> Vec<Blueprint> {
 
 fn parse_blueprint(input: &str) -> IResult<&str, Blueprint> {
     let (input, _) = tag("Blueprint ")(input)?;
-    let (input, _) = parse_u32(input)?;
+    let (input, id) = parse_u32(input)?;
     let (input, _) = tag(": Each ore robot costs ")(input)?;
     let (input, ore_robot_cost_ore) = parse_u32(input)?;
     let (input, _) = tag(" ore. Each clay robot costs ")(input)?;
@@ -185,6 +245,7 @@ fn parse_blueprint(input: &str) -> IResult<&str, Blueprint> {
     Ok((
         input,
         Blueprint {
+            id,
             ore_robot_cost_ore,
             clay_robot_cost_ore,
             obsidian_robot_cost_ore,
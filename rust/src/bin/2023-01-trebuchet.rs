@@ -1,17 +1,65 @@
+use aoc::aho_corasick::AhoCorasick;
+
 fn main() {
     println!("part 1: {:?}", part1(INPUT));
+    println!("part 2: {:?}", part2(INPUT));
 }
 
+const DIGITS: [(&str, u32); 9] = [
+    ("1", 1),
+    ("2", 2),
+    ("3", 3),
+    ("4", 4),
+    ("5", 5),
+    ("6", 6),
+    ("7", 7),
+    ("8", 8),
+    ("9", 9),
+];
+
+const DIGIT_WORDS: [(&str, u32); 9] = [
+    ("one", 1),
+    ("two", 2),
+    ("three", 3),
+    ("four", 4),
+    ("five", 5),
+    ("six", 6),
+    ("seven", 7),
+    ("eight", 8),
+    ("nine", 9),
+];
+
 fn part1(input: &str) -> u32 {
-    input.lines().map(|line| {
-        let first_dig = line.chars().find(|c| c.is_ascii_digit()).expect("no first digit");
-        let last_dig = line.chars().rev().find(|c| c.is_ascii_digit()).expect("no last digit");
+    calibration_sum(input, &AhoCorasick::new(DIGITS.iter().copied()))
+}
+
+fn part2(input: &str) -> u32 {
+    // Words can overlap, like "twone" and "eightwo", which the
+    // automaton handles since it reports overlapping matches
+    calibration_sum(input, &AhoCorasick::new(DIGITS.iter().chain(DIGIT_WORDS.iter()).copied()))
+}
 
-        let first = first_dig.to_digit(10).expect("first digit not a number");
-        let last = last_dig.to_digit(10).expect("last digit not a number");
+fn calibration_sum(input: &str, digits: &AhoCorasick<u32>) -> u32 {
+    input
+        .lines()
+        .map(|line| {
+            let (first, last) = digits.first_and_last(line).expect("no digits in line");
+            first.value * 10 + last.value
+        })
+        .sum()
+}
 
-        first * 10 + last
-    }).sum()
+#[test]
+fn test_part1() {
+    assert_eq!(part1(_EXAMPLE), 142);
+}
+
+#[test]
+fn test_part2() {
+    assert_eq!(part2(_EXAMPLE2), 281);
+    assert_eq!(part2("twone"), 21);
+    assert_eq!(part2("eightwo"), 82);
+    assert_eq!(part2("oneight3sevenine"), 19);
 }
 
 const _EXAMPLE: &str = "1abc2
@@ -19,6 +67,14 @@ pqr3stu8vwx
 a1b2c3d4e5f
 treb7uchet";
 
+const _EXAMPLE2: &str = "two1nine
+eightwothree
+abcone2threexyz
+xtwone3four
+4nineeightseven2
+zoneight234
+7pqrstsixteen";
+
 const INPUT: &str = "two65eightbkgqcsn91qxkfvg
 neightwompstbkqv1fourfthdcfgtrkqzgrbfrczxbdn
 43qsrrlxxq
@@ -0,0 +1,174 @@
+//! A circular singly linked list of distinct `u32` labels, stored as an
+//! array where each label's slot holds the label after it. Finding a label
+//! is just indexing, and moving a run of labels elsewhere only updates a
+//! few slots.
+
+/// Marks labels that aren't linked to a next label.
+const ABSENT: u32 = u32::MAX;
+
+#[derive(Debug, Clone)]
+pub struct CircularList {
+    next: Vec<u32>,
+    /// Whether each label is in the circle. Labels in a spliced out run
+    /// stay linked to each other, so `next` can't tell us this.
+    in_list: Vec<bool>,
+    len: usize,
+}
+
+/// A run of labels taken out of a list with `splice_out`. The labels stay
+/// linked to each other, so the run can be put back anywhere with
+/// `splice_in`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Run {
+    pub first: u32,
+    pub last: u32,
+    pub len: usize,
+}
+
+impl CircularList {
+    /// Links the labels into a circle in the given order. Labels must be
+    /// distinct, and the list takes space proportional to the largest.
+    pub fn from_labels<I: IntoIterator<Item = u32>>(labels: I) -> Self {
+        let mut list = CircularList { next: Vec::new(), in_list: Vec::new(), len: 0 };
+        let mut first = None;
+        let mut previous: Option<u32> = None;
+        for label in labels {
+            assert!(label != ABSENT, "label {} is reserved", ABSENT);
+            if list.next.len() <= label as usize {
+                list.next.resize(label as usize + 1, ABSENT);
+                list.in_list.resize(label as usize + 1, false);
+            }
+            assert!(!list.contains(label), "duplicate label {}", label);
+            list.in_list[label as usize] = true;
+
+            match previous {
+                Some(previous) => list.next[previous as usize] = label,
+                None => first = Some(label),
+            }
+            previous = Some(label);
+            list.len += 1;
+        }
+
+        // Close the circle
+        if let (Some(first), Some(last)) = (first, previous) {
+            list.next[last as usize] = first;
+        }
+        list
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the label is in the circle, and not in a spliced out run.
+    pub fn contains(&self, label: u32) -> bool {
+        self.in_list.get(label as usize) == Some(&true)
+    }
+
+    /// The label after `label` going around the circle.
+    pub fn next(&self, label: u32) -> u32 {
+        assert!(self.contains(label), "label {} isn't in the list", label);
+        self.next[label as usize]
+    }
+
+    /// Removes the `len` labels after `after` and returns them as a run.
+    pub fn splice_out(&mut self, after: u32, len: usize) -> Run {
+        assert!(len > 0 && len < self.len, "can't splice out {} of {} labels", len, self.len);
+        let first = self.next(after);
+        let mut last = first;
+        self.in_list[first as usize] = false;
+        for _ in 1..len {
+            last = self.next[last as usize];
+            self.in_list[last as usize] = false;
+        }
+
+        self.next[after as usize] = self.next[last as usize];
+        self.next[last as usize] = ABSENT;
+        self.len -= len;
+        Run { first, last, len }
+    }
+
+    /// Puts a run taken out with `splice_out` back in after `after`.
+    pub fn splice_in(&mut self, after: u32, run: Run) {
+        let mut label = run.first;
+        for _ in 1..run.len {
+            self.in_list[label as usize] = true;
+            label = self.next[label as usize];
+        }
+        self.in_list[run.last as usize] = true;
+
+        self.next[run.last as usize] = self.next(after);
+        self.next[after as usize] = run.first;
+        self.len += run.len;
+    }
+
+    /// Iterates over the labels in a spliced out run.
+    pub fn run_labels(&self, run: Run) -> impl Iterator<Item = u32> + '_ {
+        std::iter::successors(Some(run.first), move |&label| self.next.get(label as usize).copied())
+            .take(run.len)
+    }
+
+    /// Iterates once around the circle starting at `label`.
+    pub fn iter_from(&self, label: u32) -> impl Iterator<Item = u32> + '_ {
+        assert!(self.contains(label), "label {} isn't in the list", label);
+        std::iter::successors(Some(label), move |&label| Some(self.next(label))).take(self.len)
+    }
+}
+
+#[test]
+fn test_circular_list() {
+    let mut list = CircularList::from_labels([3, 8, 9, 1, 2]);
+    assert_eq!(list.len(), 5);
+    assert_eq!(list.iter_from(9).collect::<Vec<_>>(), vec![9, 1, 2, 3, 8]);
+    assert!(list.contains(8));
+    assert!(!list.contains(4));
+    assert!(!list.contains(100));
+
+    let run = list.splice_out(3, 2);
+    assert_eq!(run, Run { first: 8, last: 9, len: 2 });
+    assert_eq!(list.run_labels(run).collect::<Vec<_>>(), vec![8, 9]);
+    assert_eq!(list.iter_from(1).collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert!(!list.contains(8) && !list.contains(9));
+    assert!(list.contains(1) && list.contains(2) && list.contains(3));
+
+    list.splice_in(1, run);
+    assert_eq!(list.iter_from(3).collect::<Vec<_>>(), vec![3, 1, 8, 9, 2]);
+    assert!(list.contains(8) && list.contains(9));
+
+    // Splicing out across the end of the original order
+    let run = list.splice_out(9, 3);
+    assert_eq!(list.run_labels(run).collect::<Vec<_>>(), vec![2, 3, 1]);
+    assert_eq!(list.iter_from(8).collect::<Vec<_>>(), vec![8, 9]);
+    list.splice_in(8, run);
+    assert_eq!(list.iter_from(8).collect::<Vec<_>>(), vec![8, 2, 3, 1, 9]);
+}
+
+#[test]
+#[should_panic(expected = "label 8 isn't in the list")]
+fn test_circular_list_iter_from_spliced_out() {
+    let mut list = CircularList::from_labels([3, 8, 9, 1, 2]);
+    list.splice_out(3, 2);
+    let _ = list.iter_from(8);
+}
+
+#[test]
+#[should_panic(expected = "duplicate label 3")]
+fn test_circular_list_duplicate() {
+    CircularList::from_labels([3, 1, 3]);
+}
+
+#[test]
+#[should_panic(expected = "duplicate label 3")]
+fn test_circular_list_repeated_label() {
+    CircularList::from_labels([1, 3, 3]);
+}
+
+#[test]
+#[should_panic(expected = "duplicate label 1")]
+fn test_circular_list_repeated_first_label() {
+    CircularList::from_labels([1, 1]);
+}
@@ -0,0 +1,5 @@
+//! Code shared between multiple days.
+
+pub mod aho_corasick;
+pub mod circular_list;
+pub mod matrix;
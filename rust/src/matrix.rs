@@ -0,0 +1,114 @@
+//! Square matrices of counts, for stepping linear recurrences many times
+//! at once with exponentiation by squaring.
+//!
+//! Arithmetic is either exact, where overflow gives `None`, or modulo a
+//! `u64` modulus. Entries are kept below the modulus so products of two
+//! entries always fit in a `u128`.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    size: usize,
+    cells: Vec<u128>,
+}
+
+impl Matrix {
+    pub fn zero(size: usize) -> Self {
+        Matrix { size, cells: vec![0; size * size] }
+    }
+
+    pub fn identity(size: usize) -> Self {
+        let mut matrix = Matrix::zero(size);
+        for i in 0..size {
+            matrix.set(i, i, 1);
+        }
+        matrix
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> u128 {
+        self.cells[row * self.size + col]
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, value: u128) {
+        self.cells[row * self.size + col] = value;
+    }
+
+    pub fn mul(&self, other: &Matrix, modulus: Option<u64>) -> Option<Matrix> {
+        assert_eq!(self.size, other.size, "matrix sizes don't match");
+        let mut result = Matrix::zero(self.size);
+        for row in 0..self.size {
+            for k in 0..self.size {
+                let lhs = self.get(row, k);
+                // Transition matrices are mostly zeros
+                if lhs == 0 {
+                    continue;
+                }
+                for col in 0..self.size {
+                    let sum = add_mul(result.get(row, col), lhs, other.get(k, col), modulus)?;
+                    result.set(row, col, sum);
+                }
+            }
+        }
+        Some(result)
+    }
+
+    pub fn mul_vector(&self, vector: &[u128], modulus: Option<u64>) -> Option<Vec<u128>> {
+        assert_eq!(self.size, vector.len(), "vector size doesn't match matrix");
+        let mut result = vec![0; self.size];
+        for (row, sum) in result.iter_mut().enumerate() {
+            for (col, value) in vector.iter().enumerate() {
+                *sum = add_mul(*sum, self.get(row, col), *value, modulus)?;
+            }
+        }
+        Some(result)
+    }
+
+    /// Raises the matrix to a power by repeated squaring.
+    pub fn pow(&self, mut exponent: u64, modulus: Option<u64>) -> Option<Matrix> {
+        let mut result = Matrix::identity(self.size);
+        if let Some(m) = modulus {
+            result.cells.iter_mut().for_each(|cell| *cell %= m as u128);
+        }
+        let mut square = self.clone();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.mul(&square, modulus)?;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                square = square.mul(&square, modulus)?;
+            }
+        }
+        Some(result)
+    }
+}
+
+/// Computes `sum + a * b`, exactly or modulo `modulus`.
+fn add_mul(sum: u128, a: u128, b: u128, modulus: Option<u64>) -> Option<u128> {
+    match modulus {
+        Some(m) => {
+            let m = m as u128;
+            Some((sum + (a % m) * (b % m) % m) % m)
+        }
+        None => sum.checked_add(a.checked_mul(b)?),
+    }
+}
+
+#[test]
+fn test_matrix_pow() {
+    let mut fibonacci = Matrix::zero(2);
+    fibonacci.set(0, 0, 1);
+    fibonacci.set(0, 1, 1);
+    fibonacci.set(1, 0, 1);
+    assert_eq!(fibonacci.pow(0, None), Some(Matrix::identity(2)));
+    assert_eq!(fibonacci.pow(10, None).map(|m| m.get(0, 1)), Some(55));
+    assert_eq!(fibonacci.pow(90, Some(1000)).map(|m| m.get(0, 1)), Some(2880067194370816120 % 1000));
+    assert_eq!(fibonacci.pow(0, Some(1)).map(|m| m.get(0, 0)), Some(0));
+    assert_eq!(fibonacci.pow(200, None), None);
+
+    assert_eq!(fibonacci.mul_vector(&[3, 2], None), Some(vec![5, 3]));
+    assert_eq!(fibonacci.mul_vector(&[3, 2], Some(4)), Some(vec![1, 3]));
+}